@@ -1,7 +1,29 @@
 use quicksilver::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use std::collections::HashMap;
 pub mod dungeon;
-use crate::dungeon::Dungeon;
+use crate::dungeon::ascii_map;
+use crate::dungeon::boss;
+use crate::dungeon::character::{self, Definition, Definitions};
+use crate::dungeon::consumable;
+use crate::dungeon::equipment::{self, Requirement, Slot};
+use crate::dungeon::event::Event;
+use crate::dungeon::experience::{self, Perk};
+use crate::dungeon::hunger;
+use crate::dungeon::item::{ItemClass, ItemKind};
+use crate::dungeon::monster::MonsterKind;
+use crate::dungeon::perception;
+use crate::dungeon::player::Player;
+use crate::dungeon::quest::{self, Objective};
+use crate::dungeon::rest;
+use crate::dungeon::shop;
+use crate::dungeon::spell::{self, Spell, SpellEffect};
+use crate::dungeon::status::StatusKind;
+use crate::dungeon::terrain::Terrain;
+use crate::dungeon::trap::{self, TrapKind};
+use crate::dungeon::{Dungeon, Levels};
 
 #[derive(Clone, Debug, PartialEq)]
 struct Tile {
@@ -16,14 +38,11 @@ fn generate_map(d: Dungeon) -> Vec<Tile> {
     let mut map = Vec::with_capacity(width * height);
     for x in 0..width {
         for y in 0..height {
-            let mut tile = Tile {
+            let terrain = d.terrain_map[y][x];
+            let tile = Tile {
                 pos: Vector::new(x as f32, y as f32),
-                glyph: d.char_map[y][x],
-                color: Color::BLACK,
-            };
-
-            if x == 0 || x == width - 1 || y == 0 || y == height - 1 {
-                tile.glyph = '%';
+                glyph: terrain.glyph(),
+                color: terrain.color(),
             };
             map.push(tile);
         }
@@ -31,13 +50,869 @@ fn generate_map(d: Dungeon) -> Vec<Tile> {
     map
 }
 
+/// Item names are the ones the player knows them by in `d`.
+fn describe_event(event: Event, d: &Dungeon) -> String {
+    match event {
+        Event::MonsterDied { kind, .. } => format!("The {} dies.", kind.info().name),
+        Event::MonsterFled { kind, .. } => format!("The {} turns to flee!", kind.info().name),
+        Event::PlayerHit { kind, damage } if damage <= 0 => {
+            format!("The {} misses you.", kind.info().name)
+        }
+        Event::PlayerHit { kind, damage } => {
+            format!("The {} hits you for {}.", kind.info().name, damage)
+        }
+        Event::PlayerDied { kind } => format!("You are killed by the {}.", kind.info().name),
+        Event::MonsterSpat { kind } => format!("The {} spits at you.", kind.info().name),
+        Event::MonsterSummoned { kind, summoned } => format!(
+            "The {} calls a {} to its side.",
+            kind.info().name,
+            summoned.info().name
+        ),
+        Event::ItemStolen { kind, item } => {
+            let item = consumable::display_name(d, item);
+            format!("The {} steals your {}!", kind.info().name, item)
+        }
+        Event::PlayerPoisoned { kind } => format!("The {}'s bite poisons you.", kind.info().name),
+        Event::LevelUp { level } => format!("Welcome to level {}!", level),
+        Event::StatusEnded { kind } => format!("You are no longer {}.", kind.info().name),
+        Event::HungerChanged { hunger } => format!("You are {}.", hunger.info().name),
+        Event::Ate { item } => format!(
+            "You eat the {}.",
+            consumable::display_name(d, item).to_lowercase()
+        ),
+        Event::ItemUsed { item, appearance } => {
+            let verb = match item.class() {
+                ItemClass::Potion => "drink",
+                _ => "read",
+            };
+            let message = consumable::consumable_info(item).map_or("", |c| c.message);
+            match appearance {
+                Some(look) => {
+                    let unknown = match item.class() {
+                        ItemClass::Potion => format!("{} potion", look),
+                        _ => format!("scroll labelled {}", look),
+                    };
+                    format!(
+                        "You {} the {}. It was a {}! {}",
+                        verb,
+                        unknown,
+                        item.name().to_lowercase(),
+                        message
+                    )
+                }
+                None => format!(
+                    "You {} the {}. {}",
+                    verb,
+                    item.name().to_lowercase(),
+                    message
+                ),
+            }
+        }
+        Event::Equipped { item } => format!("You put on the {}.", item.name().to_lowercase()),
+        Event::Unequipped { item } => format!("You take off the {}.", item.name().to_lowercase()),
+        Event::RequirementNotMet { item, requirement } => format!(
+            "The {} needs {}.",
+            item.name().to_lowercase(),
+            requirement_text(requirement)
+        ),
+        Event::PackFull => String::from("Your pack is full."),
+        Event::GoldPickedUp { amount } => format!("You pick up {} gold.", amount),
+        Event::Unpaid { item, price } => format!(
+            "\"The {} is {} gold, please pay before you leave.\"",
+            consumable::display_name(d, item).to_lowercase(),
+            price
+        ),
+        Event::Bought { item, price } => format!(
+            "You buy the {} for {} gold.",
+            consumable::display_name(d, item).to_lowercase(),
+            price
+        ),
+        Event::Sold { item, price } => format!(
+            "You sell the {} for {} gold.",
+            consumable::display_name(d, item).to_lowercase(),
+            price
+        ),
+        Event::PutBack { item } => format!(
+            "You put the {} back.",
+            consumable::display_name(d, item).to_lowercase()
+        ),
+        Event::Paid { amount } => format!("You pay {} gold. \"Thank you!\"", amount),
+        Event::CantAfford { price } => format!("You can't afford {} gold.", price),
+        Event::NotInterested { item } => format!(
+            "\"I have no use for a {}.\"",
+            consumable::display_name(d, item).to_lowercase()
+        ),
+        Event::ShopFull => String::from("\"I have no room for that.\""),
+        Event::LeftWithoutPaying { debt } => {
+            format!("You leave without paying the {} gold you owe!", debt)
+        }
+        Event::MonsterProvoked { kind } => format!("The {} gets angry!", kind.info().name),
+        Event::TrapSprung { kind, damage } if damage > 0 => {
+            format!("{} You lose {} hp.", kind.info().message, damage)
+        }
+        Event::TrapSprung { kind, .. } => String::from(kind.info().message),
+        Event::KilledByTrap { kind } => format!("You are killed by a {}.", kind.info().name),
+        Event::MonsterSprungTrap { kind, trap } => {
+            format!("The {} {}.", kind.info().name, trap.info().monster_message)
+        }
+        Event::TrapFound { kind } => format!("You find a {}.", kind.info().name),
+        Event::NothingFound => String::from("You search but find nothing."),
+        Event::Descended { depth } => format!("You arrive on level {}.", depth),
+        Event::Ascended { depth } => format!("You climb up to level {}.", depth),
+        Event::QuestUnfinished {
+            objective: Objective::Amulet,
+        } => String::from("You won't leave without the ancient amulet."),
+        Event::QuestUnfinished {
+            objective: Objective::Boss,
+        } => String::from("You won't leave while the ogre king lives."),
+        Event::Victory => String::from("You have won!"),
+        Event::PlayerFainted => String::from("You faint from hunger."),
+        Event::PlayerStarved => String::from("You starve to death."),
+        Event::PoisonedToDeath => String::from("You die of poison."),
+        Event::SpellCast { spell } => format!("You cast {}.", spell.info().name.to_lowercase()),
+        Event::SpellHit {
+            spell: Spell::Slow,
+            kind,
+            ..
+        } => {
+            format!("The {} slows down.", kind.info().name)
+        }
+        Event::SpellHit {
+            spell,
+            kind,
+            damage,
+        } => format!(
+            "Your {} hits the {} for {}.",
+            spell.info().name.to_lowercase(),
+            kind.info().name,
+            damage
+        ),
+        Event::SpellFizzled { spell } => {
+            format!("You can't cast {} there.", spell.info().name.to_lowercase())
+        }
+        Event::NotEnoughMana { spell } => format!(
+            "You need {} mana to cast {}.",
+            spell.info().mana,
+            spell.info().name.to_lowercase()
+        ),
+        Event::Rested { turns } => format!("You rest for {} turns.", turns),
+        Event::RestInterrupted { kind: Some(kind) } => {
+            format!("You can't rest with the {} about.", kind.info().name)
+        }
+        Event::RestInterrupted { kind: None } => String::from("Your rest is disturbed."),
+        Event::TooHungryToRest => String::from("You are too hungry to rest."),
+        Event::PoisonedCantRest => String::from("You can't rest with poison in you."),
+        Event::BossPhase { kind, phase } => match boss::boss_info(kind) {
+            Some(boss) => String::from(boss.phases[phase].message),
+            None => String::new(),
+        },
+    }
+}
+
+fn requirement_text(requirement: Requirement) -> String {
+    match requirement {
+        Requirement::Level(level) => format!("level {}", level),
+        Requirement::Attack(attack) => format!("{} attack of your own", attack),
+    }
+}
+
+/// The player's attack, defence and gold with everything counted in.
+fn stats_text(player: &Player) -> String {
+    format!(
+        "Attack {}, defence {}, gold {}, [W] equipment",
+        player.attack_power(),
+        player.defence_power(),
+        player.gold
+    )
+}
+
+/// The quest, how far along it the player is, and where they are.
+fn objective_text(d: &Dungeon) -> String {
+    let goal = match d.quest.objective {
+        Objective::Amulet if d.player.has_item(ItemKind::AncientAmulet) => {
+            String::from("Goal: carry the ancient amulet up out of the dungeon")
+        }
+        Objective::Amulet => format!("Goal: find the ancient amulet on level {}", d.quest.deepest),
+        Objective::Boss => format!("Goal: kill the ogre king on level {}", d.quest.deepest),
+    };
+    let stairs = match d.terrain_map[d.player.position_x][d.player.position_y] {
+        Terrain::Stairs => ", [>] to go down",
+        Terrain::UpStairs if d.depth == 1 => ", [<] to leave",
+        Terrain::UpStairs => ", [<] to go up",
+        _ => "",
+    };
+    format!(
+        "{}\nDepth {} of {}, [S] to search{}",
+        goal, d.depth, d.quest.deepest, stairs
+    )
+}
+
+/// The run statistics shown once the player has won.
+fn victory_text(d: &Dungeon) -> String {
+    let (player, stats) = (&d.player, &d.stats);
+    let mut text = String::from("You have won!");
+    text.push_str(&format!(
+        "\nQuest: {}, level {}",
+        d.quest.objective.info().description,
+        d.quest.deepest
+    ));
+    if !player.race.is_empty() {
+        text.push_str(&format!("\nCharacter: {} {}", player.race, player.class));
+    }
+    text.push_str(&format!("\nLevel {}, {} xp", player.level, player.xp));
+    text.push_str(&format!("\nTurns taken: {}", d.turn));
+    text.push_str(&format!("\nDeepest level: {}", stats.deepest_reached));
+    text.push_str(&format!("\nMonsters killed: {}", stats.kills));
+    text.push_str(&format!(
+        "\nGold found: {}, kept {}",
+        stats.gold_found, player.gold
+    ));
+    text.push_str(&format!("\nTraps found: {}", stats.traps_found));
+    text.push_str("\n\n[Esc] to quit");
+    text
+}
+
+/// Keys for selling, one per inventory slot in pack order.
+const SELL_KEYS: [Key; 10] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+];
+
+/// What the shop has for sale and what it would pay for the player's pack,
+/// or how to start trading.
+fn shop_text(d: &Dungeon, shopping: bool) -> String {
+    if !shop::can_trade(d) {
+        return String::new();
+    }
+    if !shopping {
+        return String::from("[T] to trade with the shopkeeper");
+    }
+    let mut text = String::from("Buy with [1-9], sell with [A-J], [Esc] stops trading");
+    if let Some(shop) = d.shop.filter(|shop| shop.debt() > 0) {
+        text.push_str(&format!("\nYou owe {} gold, [P] to pay", shop.debt()));
+    }
+    text.push_str("\nFor sale:");
+    for (number, slot) in (1..).zip(shop::goods(d).into_iter().take(CHOICE_KEYS.len())) {
+        if let Some(item) = d.items[slot] {
+            let (name, price) = (consumable::display_name(d, item.kind), item.kind.price());
+            text.push_str(&format!("\n[{}] {}, {} gold", number, name, price));
+        }
+    }
+    text.push_str("\nThe shop pays:");
+    let items = d.player.inventory.iter().flatten();
+    for (letter, item) in ('A'..='J').zip(items) {
+        let name = consumable::display_name(d, *item);
+        let price = shop::sell_price(*item);
+        text.push_str(&format!("\n[{}] {}, {} gold", letter, name, price));
+    }
+    text
+}
+
+/// Something on the equipment screen: a slot, or carried gear that could
+/// go in one.
+#[derive(Copy, Clone, PartialEq)]
+enum GearEntry {
+    Worn(Slot),
+    Carried(ItemKind),
+}
+
+fn gear_entries(player: &Player) -> Vec<GearEntry> {
+    let carried = player
+        .inventory
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|&item| equipment::gear_info(item).is_some())
+        .map(GearEntry::Carried);
+    Slot::ALL
+        .iter()
+        .copied()
+        .map(GearEntry::Worn)
+        .chain(carried)
+        .collect()
+}
+
+fn gear_entry_text(player: &Player, entry: GearEntry) -> String {
+    match entry {
+        GearEntry::Worn(slot) => match player.equipment[slot as usize] {
+            Some(item) => format!("{}: {}", slot.info().name, item.name()),
+            None => format!("{}: -", slot.info().name),
+        },
+        GearEntry::Carried(item) => {
+            let mut text = format!("  {}", item.name());
+            if let Some(requirement) = equipment::unmet_requirement(player, item) {
+                text.push_str(&format!(" (needs {})", requirement_text(requirement)));
+            }
+            text
+        }
+    }
+}
+
+/// How a stat would change, as "attack 13 -> 16 (+3)".
+fn stat_change(name: &str, before: i32, after: i32) -> String {
+    if before == after {
+        return format!("{} {}", name, before);
+    }
+    format!("{} {} -> {} ({:+})", name, before, after, after - before)
+}
+
+/// What choosing an entry would do to the player's attack and defence.
+fn comparison_text(player: &Player, entry: GearEntry) -> String {
+    let (what, after) = match entry {
+        GearEntry::Worn(slot) => match player.equipment[slot as usize] {
+            Some(item) => (
+                format!("Taking off the {}", item.name().to_lowercase()),
+                equipment::take_off(*player, slot),
+            ),
+            None => return String::from("Nothing worn there."),
+        },
+        GearEntry::Carried(item) => {
+            let Some(info) = equipment::gear_info(item) else {
+                return String::new();
+            };
+            let what = match player.equipment[info.slot as usize] {
+                Some(worn) => format!(
+                    "The {} in place of the {}",
+                    item.name().to_lowercase(),
+                    worn.name().to_lowercase()
+                ),
+                None => format!("The {}", item.name().to_lowercase()),
+            };
+            (what, equipment::try_on(*player, item))
+        }
+    };
+    format!(
+        "{}: {}, {}",
+        what,
+        stat_change("attack", player.attack_power(), after.attack_power()),
+        stat_change("defence", player.defence_power(), after.defence_power())
+    )
+}
+
+fn inventory_text(d: &Dungeon) -> String {
+    let mut text = String::from("Inventory:");
+    if !usable_items(&d.player).is_empty() {
+        text = String::from("Inventory, [U] to use:");
+    }
+    let items = d.player.inventory.iter().flatten();
+    for (letter, item) in ('A'..='Z').zip(items) {
+        let name = consumable::display_name(d, *item);
+        text.push_str(&format!("\n[{}] {}", letter, name));
+    }
+    text
+}
+
+/// The potions and scrolls the player could use, in pack order.
+fn usable_items(player: &Player) -> Vec<ItemKind> {
+    player
+        .inventory
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|&item| consumable::consumable_info(item).is_some())
+        .collect()
+}
+
+/// The numbered choices while picking an item to use.
+fn use_text(d: &Dungeon) -> String {
+    let mut text = String::from("Use which item? [Esc] cancels");
+    for (number, item) in (1..).zip(usable_items(&d.player)) {
+        let name = consumable::display_name(d, item);
+        text.push_str(&format!("\n[{}] {}", number, name));
+    }
+    text
+}
+
+fn level_text(player: &Player) -> String {
+    let mut text = match experience::next_level_xp(player.level) {
+        Some(needed) => format!("Level {} ({}/{} xp)", player.level, player.xp, needed),
+        None => format!("Level {} ({} xp)", player.level, player.xp),
+    };
+    if !player.race.is_empty() {
+        text = format!("{} {}, {}", player.race, player.class, text);
+    }
+    if player.perks > 0 {
+        text.push_str("\nChoose a perk:");
+        for (number, perk) in (1..).zip(Perk::ALL) {
+            let info = perk.info();
+            text.push_str(&format!(
+                "\n[{}] {}: {}",
+                number, info.name, info.description
+            ));
+        }
+    }
+    text
+}
+
+/// What the player is told while casting, or how to start.
+fn spell_text(player: &Player, casting: Option<Casting>) -> String {
+    match casting {
+        Some(Casting::Choosing) => {
+            let mut text = String::from("Cast which spell? [Esc] cancels");
+            let spells = player.spells.iter().flatten();
+            for (number, spell) in (1..).zip(spells) {
+                let info = spell.info();
+                text.push_str(&format!(
+                    "\n[{}] {} ({} mana): {}",
+                    number, info.name, info.mana, info.description
+                ));
+            }
+            text
+        }
+        Some(Casting::Aiming(spell)) => format!(
+            "Aiming {}: arrows move, [Enter] casts, [Esc] cancels",
+            spell.info().name.to_lowercase()
+        ),
+        None if player.spells.iter().any(|s| s.is_some()) => {
+            format!("Mana {}/{}, [C] to cast", player.mana, player.max_mana)
+        }
+        None => String::new(),
+    }
+}
+
+/// Keys for choosing perks, in `Perk::ALL` order.
+const PERK_KEYS: [Key; 3] = [Key::Key1, Key::Key2, Key::Key3];
+
+/// Keys for picking a race or class, in definition order.
+const CHOICE_KEYS: [Key; 9] = [
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+/// Where casting a spell is up to.
+#[derive(Copy, Clone)]
+enum Casting {
+    /// The spellbook is open, waiting for a spell to be picked.
+    Choosing,
+    /// Moving the target, kept in `Player::target`, for a spell.
+    Aiming(Spell),
+}
+
+/// The equipment screen, open while the player looks over their gear.
+struct EquipmentScreen {
+    /// Index into `gear_entries` of the entry compared against what is worn.
+    hovered: usize,
+    /// Where each entry was last drawn, for hovering with the mouse.
+    rows: Vec<Rectangle>,
+    /// Where the mouse was, so it only moves the cursor when it moves.
+    mouse_px: Vector,
+}
+
+/// Height of a line on the equipment screen.
+const GEAR_ROW_PX: f32 = 26.0;
+
+/// How far character creation has got, see `Game::finish_creation`.
+struct Creation {
+    race: Option<usize>,
+    class: Option<usize>,
+}
+
+/// The numbered choices for whichever of race and class is picked next.
+fn creation_text(definitions: &Definitions, creation: &Creation) -> String {
+    let (what, choices): (&str, &[Definition]) = match creation.race {
+        None => ("race", &definitions.races),
+        Some(_) => ("class", &definitions.classes),
+    };
+    let mut text = format!("Choose your {}:", what);
+    for (number, choice) in (1..).zip(choices.iter().take(CHOICE_KEYS.len())) {
+        text.push_str(&format!("\n[{}] {}", number, choice.summary()));
+    }
+    text
+}
+
+/// The value following a command line flag, as in `--race elf`.
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    let at = args.iter().position(|arg| arg == flag)?;
+    args.get(at + 1).cloned()
+}
+
+/// Say how a flag is used and quit, for a value given on the command line
+/// that makes no sense.
+fn usage(message: &str) -> ! {
+    eprintln!("usage: {}", message);
+    std::process::exit(1)
+}
+
+/// The four ways the player can step, as `Dungeon::target_cell` takes them.
+const DIRECTIONS: [(char, usize); 4] = [('x', 1000), ('x', 1), ('y', 1000), ('y', 1)];
+
+/// Mixed into the seed for which way a confused player stumbles.
+const CONFUSION_SALT: u64 = 0xc0f;
+
+/// How many lines of the message log are kept on screen.
+const MAX_MESSAGES: usize = 5;
+
 pub struct Game {
     title: Asset<Image>,
-    inventory: Asset<Image>,
+    font: Asset<Font>,
     map: Vec<Tile>,
     tileset: Asset<HashMap<char, Image>>,
     tile_size_px: Vector,
     dungeon: Dungeon,
+    /// The other levels of the dungeon, as the player left them.
+    levels: Levels,
+    messages: Vec<String>,
+    definitions: Definitions,
+    /// `None` once the character has been created and play has started.
+    creation: Option<Creation>,
+    casting: Option<Casting>,
+    /// Whether the player is picking a potion or scroll to use.
+    using_item: bool,
+    equipment: Option<EquipmentScreen>,
+    /// Whether the player is trading in a shop.
+    shopping: bool,
+}
+
+impl Game {
+    /// Move the player one cell, opening a door or targeting a monster if
+    /// something is in the way. Stunned players lose the turn, and confused
+    /// ones go any which way.
+    fn try_move(&mut self, direction: char, value: usize) {
+        if !self.dungeon.player.alive {
+            return;
+        }
+        if self.dungeon.player.statuses.has(StatusKind::Stun) {
+            self.end_turn();
+            return;
+        }
+        let (direction, value) = if self.dungeon.player.statuses.has(StatusKind::Confusion) {
+            let d = &self.dungeon;
+            let mut rng = StdRng::seed_from_u64(d.seed ^ ((d.turn as u64) << 8) ^ CONFUSION_SALT);
+            *DIRECTIONS.choose(&mut rng).unwrap_or(&(direction, value))
+        } else {
+            (direction, value)
+        };
+        let (x, y) = self.dungeon.target_cell(direction, value);
+        self.dungeon = dungeon::monster_map(self.dungeon);
+        if self.dungeon.move_character(direction, value) {
+            self.dungeon.player.position_x = x;
+            self.dungeon.player.position_y = y;
+            self.dungeon = dungeon::pick_up_items(self.dungeon);
+            self.dungeon = trap::enter_cell(self.dungeon);
+            self.dungeon = perception::make_noise(self.dungeon, perception::NOISE_WALK);
+            self.end_turn();
+        } else {
+            let (d, opened) = dungeon::open_door(self.dungeon, x, y);
+            self.dungeon = d;
+            let (d, dug) = dungeon::dig(self.dungeon, x, y, self.dungeon.player.dig_power());
+            self.dungeon = d;
+            if opened {
+                self.dungeon = perception::make_noise(self.dungeon, perception::NOISE_DOOR);
+            }
+            if dug {
+                self.dungeon = perception::make_noise(self.dungeon, perception::NOISE_DIG);
+            }
+            if opened || dug {
+                self.end_turn();
+            } else {
+                // Process Target Monster
+                self.dungeon.player = dungeon::process_target_monster(self.dungeon.player, x, y);
+            }
+        }
+    }
+
+    fn open_adjacent_doors(&mut self) {
+        if !self.dungeon.player.alive {
+            return;
+        }
+        let mut opened_any = false;
+        for (direction, value) in DIRECTIONS {
+            let (x, y) = self.dungeon.target_cell(direction, value);
+            let (d, opened) = dungeon::open_door(self.dungeon, x, y);
+            self.dungeon = d;
+            opened_any |= opened;
+        }
+        if opened_any {
+            self.dungeon = perception::make_noise(self.dungeon, perception::NOISE_DOOR);
+            self.dungeon = dungeon::monster_map(self.dungeon);
+            self.end_turn();
+        }
+    }
+
+    /// Rest until healed or disturbed. The turns are played out by the
+    /// dungeon, so the screen only catches up at the end.
+    fn rest(&mut self) {
+        self.dungeon = rest::rest(self.dungeon);
+        self.map = generate_map(self.dungeon);
+    }
+
+    /// Look for hidden traps nearby, which takes a turn.
+    fn search(&mut self) {
+        if !self.dungeon.player.alive {
+            return;
+        }
+        self.dungeon = trap::search(self.dungeon);
+        self.end_turn();
+    }
+
+    /// Take the stairs underfoot, which takes a turn if the player went
+    /// anywhere.
+    fn take_stairs(&mut self) {
+        let (d, moved) = dungeon::take_stairs(self.dungeon);
+        self.dungeon = d;
+        if moved {
+            self.end_turn();
+        }
+    }
+
+    /// Eat something, which takes a turn if there was anything to eat.
+    fn eat(&mut self) {
+        if !self.dungeon.player.alive {
+            return;
+        }
+        let (d, ate) = hunger::eat(self.dungeon);
+        self.dungeon = d;
+        if ate {
+            self.end_turn();
+        }
+    }
+
+    /// Pick a spell or move its target, then cast it. Escape backs out.
+    fn update_casting(&mut self, window: &mut Window) {
+        use ButtonState::*;
+
+        let keyboard = window.keyboard();
+        if keyboard[Key::Escape] == Pressed {
+            self.casting = None;
+            return;
+        }
+        match self.casting {
+            Some(Casting::Choosing) => {
+                let spells = self.dungeon.player.spells;
+                let picked = CHOICE_KEYS
+                    .iter()
+                    .zip(spells.iter().flatten().copied())
+                    .find(|(key, _)| keyboard[**key] == Pressed)
+                    .map(|(_, spell)| spell);
+                match picked {
+                    Some(spell) if spell.targeted() => {
+                        self.dungeon.player.target = self.initial_target(spell);
+                        self.casting = Some(Casting::Aiming(spell));
+                    }
+                    Some(spell) => {
+                        let player = &self.dungeon.player;
+                        self.cast(spell, (player.position_x, player.position_y));
+                    }
+                    None => {}
+                }
+            }
+            Some(Casting::Aiming(spell)) => {
+                let (x, y) = self.dungeon.player.target;
+                let target = if keyboard[Key::Left] == Pressed {
+                    (x, y.saturating_sub(1))
+                } else if keyboard[Key::Right] == Pressed {
+                    (x, (y + 1).min(self.dungeon.dungeon_y - 1))
+                } else if keyboard[Key::Up] == Pressed {
+                    (x.saturating_sub(1), y)
+                } else if keyboard[Key::Down] == Pressed {
+                    ((x + 1).min(self.dungeon.dungeon_x - 1), y)
+                } else {
+                    (x, y)
+                };
+                self.dungeon.player.target = target;
+                if keyboard[Key::Return] == Pressed {
+                    self.cast(spell, target);
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Where aiming starts: the monster last targeted if it is still in
+    /// sight, else the nearest one in sight, else the player. Blinks always
+    /// start from the player.
+    fn initial_target(&self, spell: Spell) -> (usize, usize) {
+        let d = &self.dungeon;
+        let player = (d.player.position_x, d.player.position_y);
+        if let SpellEffect::Blink { .. } = spell.info().effect {
+            return player;
+        }
+        let visible: Vec<(usize, usize)> = d
+            .monsters
+            .iter()
+            .filter(|m| m.alive && d.line_of_sight(player, (m.pos_x, m.pos_y)))
+            .map(|m| (m.pos_x, m.pos_y))
+            .collect();
+        if visible.contains(&d.player.target) {
+            return d.player.target;
+        }
+        visible
+            .into_iter()
+            .min_by_key(|&(x, y)| x.abs_diff(player.0).max(y.abs_diff(player.1)))
+            .unwrap_or(player)
+    }
+
+    /// Cast a spell, which takes the turn if it worked. Stunned players just
+    /// lose the turn.
+    fn cast(&mut self, spell: Spell, target: (usize, usize)) {
+        self.casting = None;
+        if !self.dungeon.player.alive {
+            return;
+        }
+        if self.dungeon.player.statuses.has(StatusKind::Stun) {
+            self.end_turn();
+            return;
+        }
+        let (d, cast) = spell::cast(self.dungeon, spell, target);
+        self.dungeon = d;
+        if cast {
+            self.dungeon = perception::make_noise(self.dungeon, perception::NOISE_SPELL);
+            self.dungeon = dungeon::monster_map(self.dungeon);
+            self.end_turn();
+        }
+    }
+
+    /// Pick a potion or scroll and use it, which takes the turn. Escape backs
+    /// out.
+    fn update_using(&mut self, window: &mut Window) {
+        use ButtonState::*;
+
+        let keyboard = window.keyboard();
+        if keyboard[Key::Escape] == Pressed {
+            self.using_item = false;
+            return;
+        }
+        let picked = CHOICE_KEYS
+            .iter()
+            .zip(usable_items(&self.dungeon.player))
+            .find(|(key, _)| keyboard[**key] == Pressed)
+            .map(|(_, item)| item);
+        let Some(item) = picked else {
+            return;
+        };
+        self.using_item = false;
+        let (d, used) = consumable::use_item(self.dungeon, item);
+        self.dungeon = d;
+        if used {
+            self.end_turn();
+        }
+    }
+
+    /// Move the cursor with the arrows or the mouse, and equip or take off
+    /// the entry under it with Enter or a click, which takes the turn. Escape
+    /// closes the screen.
+    fn update_equipment(&mut self, window: &mut Window) {
+        use ButtonState::*;
+
+        let Some(screen) = &mut self.equipment else {
+            return;
+        };
+        if window.keyboard()[Key::Escape] == Pressed {
+            self.equipment = None;
+            return;
+        }
+        let entries = gear_entries(&self.dungeon.player);
+        let mouse_px = window.mouse().pos();
+        let under_mouse = screen.rows.iter().position(|r| r.contains(mouse_px));
+        let clicked = window.mouse()[MouseButton::Left] == Pressed && under_mouse.is_some();
+        if mouse_px != screen.mouse_px || clicked {
+            screen.mouse_px = mouse_px;
+            screen.hovered = under_mouse.unwrap_or(screen.hovered);
+        }
+        if window.keyboard()[Key::Up] == Pressed {
+            screen.hovered = screen.hovered.saturating_sub(1);
+        }
+        if window.keyboard()[Key::Down] == Pressed {
+            screen.hovered += 1;
+        }
+        screen.hovered = screen.hovered.min(entries.len() - 1);
+
+        if window.keyboard()[Key::Return] != Pressed && !clicked {
+            return;
+        }
+        let (d, changed) = match entries[screen.hovered] {
+            GearEntry::Worn(slot) => equipment::unequip(self.dungeon, slot),
+            GearEntry::Carried(item) => equipment::equip(self.dungeon, item),
+        };
+        self.dungeon = d;
+        if changed {
+            self.end_turn();
+        }
+    }
+
+    /// Buy, sell and pay with the shop's keys; trading takes no time. Escape
+    /// stops, as does the shop closing.
+    fn update_shopping(&mut self, window: &mut Window) {
+        use ButtonState::*;
+
+        let keyboard = window.keyboard();
+        if keyboard[Key::Escape] == Pressed || !shop::can_trade(&self.dungeon) {
+            self.shopping = false;
+            return;
+        }
+        let goods = shop::goods(&self.dungeon);
+        if let Some((_, &slot)) = CHOICE_KEYS
+            .iter()
+            .zip(goods.iter())
+            .find(|(key, _)| keyboard[**key] == Pressed)
+        {
+            self.dungeon = shop::buy(self.dungeon, slot);
+        }
+        let inventory = self.dungeon.player.inventory;
+        let carried: Vec<ItemKind> = inventory.iter().flatten().copied().collect();
+        if let Some((_, &item)) = SELL_KEYS
+            .iter()
+            .zip(carried.iter())
+            .find(|(key, _)| keyboard[**key] == Pressed)
+        {
+            self.dungeon = shop::sell(self.dungeon, item);
+        }
+        if keyboard[Key::P] == Pressed {
+            self.dungeon = shop::pay(self.dungeon);
+        }
+    }
+
+    /// Move the dungeon's events into the message log.
+    fn collect_messages(&mut self) {
+        let (d, events) = dungeon::take_events(self.dungeon);
+        self.dungeon = d;
+        for event in events {
+            self.messages.push(describe_event(event, &self.dungeon));
+        }
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.drain(..self.messages.len() - MAX_MESSAGES);
+        }
+    }
+
+    /// Start the game as the chosen character once both a race and a class
+    /// are picked.
+    fn finish_creation(&mut self) {
+        let Some(creation) = &self.creation else {
+            return;
+        };
+        if let (Some(race), Some(class)) = (creation.race, creation.class) {
+            let (race, class) = (
+                &self.definitions.races[race],
+                &self.definitions.classes[class],
+            );
+            self.dungeon = character::create_character(self.dungeon, race, class);
+            self.creation = None;
+        }
+    }
+
+    /// Take the player to another level if they are leaving this one, let
+    /// the monsters act and refresh everything derived from the dungeon.
+    fn end_turn(&mut self) {
+        self.dungeon = self.levels.travel(self.dungeon);
+        self.dungeon = dungeon::take_turn(self.dungeon);
+        self.dungeon = dungeon::calculate_distance_map(self.dungeon);
+        self.map = generate_map(self.dungeon);
+    }
 }
 
 impl State for Game {
@@ -52,23 +927,77 @@ impl State for Game {
                 font.render("Learning Rust ", &FontStyle::new(72.0, Color::BLACK))
             }));
 
-        let dungeon: Dungeon = dungeon::generate_dungeon();
-        let inventory = Asset::new(Font::load(font_mononoki).and_then(move |font| {
-            font.render(
-                "Inventory:\n[A] Sword\n[B] Shield\n[C] Darts",
-                &FontStyle::new(20.0, Color::BLACK),
-            )
-        }));
+        // A hand-written map can be given on the command line, and the race
+        // and class with `--race` and `--class` to skip choosing them. The
+        // quest is set with `--objective` and how deep it goes with
+        // `--deepest`
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let mut quest = quest::DEFAULT_QUEST;
+        if let Some(name) = flag_value(&args, "--objective") {
+            quest.objective = Objective::from_name(&name).unwrap_or_else(|| {
+                let names: Vec<_> = Objective::ALL.iter().map(|o| o.info().name).collect();
+                usage(&format!(
+                    "--objective NAME, where NAME is one of {}",
+                    names.join(", ")
+                ))
+            });
+        }
+        if let Some(deepest) = flag_value(&args, "--deepest") {
+            quest.deepest = deepest
+                .parse()
+                .ok()
+                .filter(|&deepest| deepest > 0)
+                .unwrap_or_else(|| usage("--deepest N, where N is how many levels down it goes"));
+        }
+        let map_path = args
+            .iter()
+            .enumerate()
+            .find(|&(i, arg)| !arg.starts_with("--") && (i == 0 || !args[i - 1].starts_with("--")))
+            .map(|(_, arg)| arg.clone());
+        let dungeon: Dungeon = match map_path {
+            Some(path) => {
+                let text = std::fs::read_to_string(&path)?;
+                let mut d = ascii_map::load_map(&text)
+                    .unwrap_or_else(|err| panic!("Could not load map {}: {}", path, err));
+                d.quest = quest;
+                d
+            }
+            None => dungeon::generate_dungeon(quest),
+        };
+        let font = Asset::new(Font::load(font_mononoki));
         let map = generate_map(dungeon);
 
+        let definitions = character::definitions();
+        let pick = |flag: &str, choices: &[Definition]| {
+            flag_value(&args, flag).map(|name| {
+                choices
+                    .iter()
+                    .position(|c| c.name.eq_ignore_ascii_case(&name))
+                    .unwrap_or_else(|| {
+                        usage(&format!(
+                            "{} NAME, where --characters lists the names to pick from",
+                            flag
+                        ))
+                    })
+            })
+        };
+        let creation = Creation {
+            race: pick("--race", &definitions.races),
+            class: pick("--class", &definitions.classes),
+        };
+
         // The Square font: http://strlen.com/square/?s[]=font
         // License: CC BY 3.0 https://creativecommons.org/licenses/by/3.0/deed.en_US
         let font_square = "square.ttf";
-        let game_glyphs = "#@g.%-";
+        let mut game_glyphs: String = Terrain::ALL.iter().map(|t| t.glyph()).collect();
+        game_glyphs.push('@');
+        game_glyphs.extend(MonsterKind::ALL.iter().map(|m| m.info().character));
+        game_glyphs.extend(ItemKind::ALL.iter().map(|i| i.glyph()));
+        game_glyphs.extend(TrapKind::ALL.iter().map(|t| t.info().glyph));
         let tile_size_px = Vector::new(24, 24);
         let tileset = Asset::new(Font::load(font_square).and_then(move |text| {
             let tiles = text
-                .render(game_glyphs, &FontStyle::new(tile_size_px.y, Color::WHITE))
+                .render(&game_glyphs, &FontStyle::new(tile_size_px.y, Color::WHITE))
                 .expect("Could not render the font tileset.");
             let mut tileset = HashMap::new();
             for (index, glyph) in game_glyphs.chars().enumerate() {
@@ -79,87 +1008,142 @@ impl State for Game {
             Ok(tileset)
         }));
 
-        Ok(Self {
+        let mut game = Self {
             title,
-            inventory,
+            font,
             map,
             tileset,
             tile_size_px,
             dungeon,
-        })
+            levels: Levels::default(),
+            messages: Vec::new(),
+            definitions,
+            creation: Some(creation),
+            casting: None,
+            using_item: false,
+            equipment: None,
+            shopping: false,
+        };
+        // Both may have been given on the command line
+        game.finish_creation();
+        Ok(game)
     }
 
     /// Process keyboard and mouse, update the game state
     fn update(&mut self, window: &mut Window) -> Result<()> {
         use ButtonState::*;
 
-        if window.keyboard()[Key::Left] == Pressed {
-            self.dungeon = dungeon::monster_map(self.dungeon);
-            if self.dungeon.move_character('x', 1000) {
-                self.dungeon.player.position_y -= 1;
-
-                self.dungeon = dungeon::process_monster_moves_attack(self.dungeon);
-                self.dungeon = dungeon::calculate_distance_map(self.dungeon);
-            } else {
-                // Process Target Monster
-                self.dungeon.player = dungeon::process_target_monster(self.dungeon.player, 
-                    self.dungeon.player.position_x, 
-                    self.dungeon.player.position_y - 1);
-       
+        if let Some(creation) = &mut self.creation {
+            for (i, key) in CHOICE_KEYS.iter().enumerate() {
+                if window.keyboard()[*key] != Pressed {
+                    continue;
+                }
+                if creation.race.is_none() && i < self.definitions.races.len() {
+                    creation.race = Some(i);
+                } else if creation.race.is_some() && i < self.definitions.classes.len() {
+                    creation.class = Some(i);
+                }
+                break;
+            }
+            self.finish_creation();
+            if window.keyboard()[Key::Escape].is_down() {
+                window.close();
             }
+            return Ok(());
         }
-        if window.keyboard()[Key::Right] == Pressed {
-            self.dungeon = dungeon::monster_map(self.dungeon);
-            if self.dungeon.move_character('x', 1) {
-                self.dungeon.player.position_y += 1;
-
-                self.dungeon = dungeon::process_monster_moves_attack(self.dungeon);
-                self.dungeon = dungeon::calculate_distance_map(self.dungeon);
-            } else {
-                // Process Target Monster
-                self.dungeon.player = dungeon::process_target_monster(self.dungeon.player, 
-                    self.dungeon.player.position_x, 
-                    self.dungeon.player.position_y + 1);
-                
+        // Nothing left to do once the game is won
+        if self.dungeon.won {
+            if window.keyboard()[Key::Escape] == Pressed {
+                window.close();
             }
+            return Ok(());
+        }
+        if self.casting.is_some() {
+            self.update_casting(window);
+            self.collect_messages();
+            return Ok(());
+        }
+        if self.using_item {
+            self.update_using(window);
+            self.collect_messages();
+            return Ok(());
+        }
+        if self.equipment.is_some() {
+            self.update_equipment(window);
+            self.collect_messages();
+            return Ok(());
+        }
+        if self.shopping {
+            self.update_shopping(window);
+            self.collect_messages();
+            return Ok(());
         }
-        if window.keyboard()[Key::Up] == Pressed {
-            self.dungeon = dungeon::monster_map(self.dungeon);
-            if self.dungeon.move_character('y', 1000) {
-                self.dungeon.player.position_x -= 1;
 
-                self.dungeon = dungeon::process_monster_moves_attack(self.dungeon);
-                self.dungeon = dungeon::calculate_distance_map(self.dungeon);
-            } else {
-                // Process Target Monster
-                self.dungeon.player = dungeon::process_target_monster(self.dungeon.player, 
-                    self.dungeon.player.position_x - 1, 
-                    self.dungeon.player.position_y);
-            }
+        if window.keyboard()[Key::Left] == Pressed {
+            self.try_move('x', 1000);
+        }
+        if window.keyboard()[Key::Right] == Pressed {
+            self.try_move('x', 1);
+        }
+        if window.keyboard()[Key::Up] == Pressed {
+            self.try_move('y', 1000);
         }
         if window.keyboard()[Key::Down] == Pressed {
-            self.dungeon = dungeon::monster_map(self.dungeon);
-            if self.dungeon.move_character('y', 1) {
-                self.dungeon.player.position_x += 1;
-
-                self.dungeon = dungeon::process_monster_moves_attack(self.dungeon);
-                self.dungeon = dungeon::calculate_distance_map(self.dungeon);
-            } else {
-                // Process Target Monster
-                self.dungeon.player = dungeon::process_target_monster(self.dungeon.player, 
-                    self.dungeon.player.position_x + 1, 
-                    self.dungeon.player.position_y);
+            self.try_move('y', 1);
+        }
+        if window.keyboard()[Key::O] == Pressed {
+            self.open_adjacent_doors();
+        }
+        if window.keyboard()[Key::R] == Pressed {
+            self.rest();
+        }
+        if window.keyboard()[Key::E] == Pressed {
+            self.eat();
+        }
+        if window.keyboard()[Key::S] == Pressed {
+            self.search();
+        }
+        // Shifted, these are '>' and '<'
+        if window.keyboard()[Key::Period] == Pressed || window.keyboard()[Key::Comma] == Pressed {
+            self.take_stairs();
+        }
+        let has_usable = !usable_items(&self.dungeon.player).is_empty();
+        if window.keyboard()[Key::U] == Pressed && has_usable && self.dungeon.player.alive {
+            self.using_item = true;
+        }
+        if window.keyboard()[Key::T] == Pressed && shop::can_trade(&self.dungeon) {
+            self.shopping = true;
+        }
+        if window.keyboard()[Key::W] == Pressed && self.dungeon.player.alive {
+            self.equipment = Some(EquipmentScreen {
+                hovered: 0,
+                rows: Vec::new(),
+                mouse_px: window.mouse().pos(),
+            });
+        }
+        let has_spells = self.dungeon.player.spells.iter().any(|s| s.is_some());
+        if window.keyboard()[Key::C] == Pressed && has_spells && self.dungeon.player.alive {
+            self.casting = Some(Casting::Choosing);
+        }
+        for (key, perk) in PERK_KEYS.iter().zip(Perk::ALL) {
+            if window.keyboard()[*key] == Pressed {
+                self.dungeon = experience::choose_perk(self.dungeon, perk);
             }
         }
-        if window.keyboard()[Key::Escape].is_down() {
+        if window.keyboard()[Key::F2] == Pressed {
+            std::fs::write("dungeon.txt", ascii_map::export_map(&self.dungeon))?;
+        }
+        // Pressed rather than held, so backing out of casting doesn't quit
+        if window.keyboard()[Key::Escape] == Pressed {
             window.close();
         }
-        if window.keyboard()[Key::A].is_down() {
-            let return_tup = dungeon::process_attack(self.dungeon);
-            if return_tup.2 {
-                self.dungeon.monsters[return_tup.1] = return_tup.0;
-            }
+        if window.keyboard()[Key::A].is_down() && self.dungeon.player.alive {
+            self.dungeon = dungeon::process_attack(self.dungeon);
+            self.dungeon = perception::make_noise(self.dungeon, perception::NOISE_ATTACK);
+            self.map = generate_map(self.dungeon);
         }
+
+        self.collect_messages();
         Ok(())
     }
 
@@ -173,7 +1157,7 @@ impl State for Game {
                 &image
                     .area()
                     .with_center((window.screen_size().x as i32 / 2, 40)),
-                Img(&image),
+                Img(image),
             );
             Ok(())
         })?;
@@ -181,6 +1165,28 @@ impl State for Game {
         let tile_size_px = self.tile_size_px;
         let offset_px = Vector::new(50, 120);
 
+        // The creation screen stands in for the game until it is done
+        if let Some(creation) = &self.creation {
+            let text = creation_text(&self.definitions, creation);
+            self.font.execute(|font| {
+                let image = font.render(&text, &FontStyle::new(24.0, Color::BLACK))?;
+                window.draw(&image.area().translate(offset_px), Img(&image));
+                Ok(())
+            })?;
+            return Ok(());
+        }
+
+        // So does the victory screen once the game is won
+        if self.dungeon.won {
+            let text = victory_text(&self.dungeon);
+            self.font.execute(|font| {
+                let image = font.render(&text, &FontStyle::new(24.0, Color::BLACK))?;
+                window.draw(&image.area().translate(offset_px), Img(&image));
+                Ok(())
+            })?;
+            return Ok(());
+        }
+
         // Draw the map
         let (tileset, map) = (&mut self.tileset, &self.map);
         tileset.execute(|tileset| {
@@ -189,25 +1195,55 @@ impl State for Game {
                     let pos_px = tile.pos.times(tile_size_px);
                     window.draw(
                         &Rectangle::new(offset_px + pos_px, image.area().size()),
-                        Blended(&image, tile.color),
+                        Blended(image, tile.color),
                     );
                 }
             }
             Ok(())
         })?;
 
-        // Draw Monsters and PC 
+        // Draw found Traps, Items, Monsters and PC
         let (tileset, d) = (&mut self.tileset, &self.dungeon);
         tileset.execute(|tileset| {
+            for trap in d.traps.iter().flatten().filter(|t| t.found) {
+                let info = trap.kind.info();
+                if let Some(image) = tileset.get(&info.glyph) {
+                    let trap_vector = Vector::new(trap.pos_y as i32, trap.pos_x as i32);
+                    let pos_px = offset_px + trap_vector.times(tile_size_px);
+                    window.draw(
+                        &Rectangle::new(pos_px, image.area().size()),
+                        Blended(image, info.color),
+                    );
+                }
+            }
+            for item in d.items.iter().flatten() {
+                if let Some(image) = tileset.get(&item.kind.glyph()) {
+                    let item_vector = Vector::new(item.pos_y as i32, item.pos_x as i32);
+                    let pos_px = offset_px + item_vector.times(tile_size_px);
+                    window.draw(
+                        &Rectangle::new(pos_px, image.area().size()),
+                        Blended(image, item.kind.color()),
+                    );
+                }
+            }
+            let player_pos = (d.player.position_x, d.player.position_y);
             for i in 0..d.monsters.len() {
                 if let Some(image) = tileset.get(&d.monsters[i].character) {
                     let mon_vector =
                         Vector::new(d.monsters[i].pos_y as i32, d.monsters[i].pos_x as i32);
                     let pos_px = offset_px + mon_vector.times(tile_size_px);
-                    if d.monsters[i].alive {
+                    let mon_pos = (d.monsters[i].pos_x, d.monsters[i].pos_y);
+                    let seen = d.mapped || d.line_of_sight(player_pos, mon_pos);
+                    if d.monsters[i].alive && seen {
+                        // Sleeping monsters are drawn faded
+                        let color = if d.monsters[i].awake {
+                            d.monsters[i].color
+                        } else {
+                            d.monsters[i].color.with_alpha(0.4)
+                        };
                         window.draw(
                             &Rectangle::new(pos_px, image.area().size()),
-                            Blended(&image, d.monsters[i].color),
+                            Blended(image, color),
                         );
                     }
                 }
@@ -219,20 +1255,75 @@ impl State for Game {
                     let pos_px = offset_px + player_vector.times(tile_size_px);
                     window.draw(
                         &Rectangle::new(pos_px, image.area().size()),
-                        Blended(&image, d.player.color),
+                        Blended(image, d.player.color),
                     );
                 }
             }
             Ok(())
         })?;
 
+        // The spell target, and everything a blast there would catch
+        if let Some(Casting::Aiming(spell)) = self.casting {
+            let (tx, ty) = self.dungeon.player.target;
+            let radius = match spell.info().effect {
+                SpellEffect::Blast { radius, .. } => radius,
+                _ => 0,
+            };
+            for x in tx.saturating_sub(radius)..=tx + radius {
+                for y in ty.saturating_sub(radius)..=ty + radius {
+                    let alpha = if (x, y) == (tx, ty) { 0.6 } else { 0.3 };
+                    let cell_px = offset_px + Vector::new(y as f32, x as f32).times(tile_size_px);
+                    window.draw(
+                        &Rectangle::new(cell_px, tile_size_px),
+                        Col(Color::YELLOW.with_alpha(alpha)),
+                    );
+                }
+            }
+        }
+
+        let map_size = Vector::new(self.dungeon.dungeon_y as i32, self.dungeon.dungeon_x as i32);
+        let map_size_px = map_size.times(tile_size_px);
+
+        // The equipment screen covers the map, one line per entry so the
+        // mouse can find them, then the hovered entry compared to what's worn
+        if let Some(screen) = &mut self.equipment {
+            window.draw(&Rectangle::new(offset_px, map_size_px), Col(Color::WHITE));
+            let player = &self.dungeon.player;
+            let entries = gear_entries(player);
+            let hovered = entries[screen.hovered.min(entries.len() - 1)];
+            let header = "Equipment: arrows or mouse pick, [Enter] or click to put on or \
+                          take off, [Esc] closes";
+            let mut lines = vec![(String::from(header), Color::BLACK)];
+            for &entry in entries.iter() {
+                let color = if entry == hovered {
+                    Color::BLUE
+                } else {
+                    Color::BLACK
+                };
+                lines.push((gear_entry_text(player, entry), color));
+            }
+            lines.push((comparison_text(player, hovered), Color::BLACK));
+            screen.rows.clear();
+            self.font.execute(|font| {
+                for (i, (line, color)) in lines.iter().enumerate() {
+                    let pos_px = offset_px + Vector::new(0.0, i as f32 * GEAR_ROW_PX);
+                    let image = font.render(line, &FontStyle::new(20.0, *color))?;
+                    window.draw(&image.area().translate(pos_px), Img(&image));
+                    // The first line is the header and the last the comparison
+                    if i > 0 && i < lines.len() - 1 {
+                        let row = Rectangle::new(pos_px, (map_size_px.x, GEAR_ROW_PX));
+                        screen.rows.push(row);
+                    }
+                }
+                Ok(())
+            })?;
+        }
+
         let player = &self.dungeon.player;
         let full_health_width_px = 100.0;
         let current_health_width_px =
             (player.hp as f32 / player.max_hp as f32) * full_health_width_px;
 
-        let map_size = Vector::new(self.dungeon.dungeon_y as i32, self.dungeon.dungeon_x as i32);
-        let map_size_px = map_size.times(tile_size_px);
         let health_bar_pos_px = offset_px + Vector::new(map_size_px.x, 0.0);
 
         // Full health
@@ -247,16 +1338,138 @@ impl State for Game {
             Col(Color::RED),
         );
 
-        self.inventory.execute(|image| {
+        // The boss's health, named on the bar, once its fight has started
+        let boss = boss::find_boss(&self.dungeon).map(|i| self.dungeon.monsters[i]);
+        let boss_bar_pos_px = health_bar_pos_px + Vector::new(full_health_width_px + 20.0, 0.0);
+        let full_boss_width_px = 200.0;
+        if let Some(boss) = boss.filter(|b| b.phase.is_some()) {
+            window.draw(
+                &Rectangle::new(boss_bar_pos_px, (full_boss_width_px, tile_size_px.y)),
+                Col(boss.color.with_alpha(0.5)),
+            );
+            window.draw(
+                &Rectangle::new(
+                    boss_bar_pos_px,
+                    (
+                        (boss.hp.max(0) as f32 / boss.max_hp as f32) * full_boss_width_px,
+                        tile_size_px.y,
+                    ),
+                ),
+                Col(boss.color),
+            );
+            let name = boss.kind.info().name;
+            self.font.execute(|font| {
+                let image = font.render(name, &FontStyle::new(20.0, Color::BLACK))?;
+                window.draw(&image.area().translate(boss_bar_pos_px), Img(&image));
+                Ok(())
+            })?;
+        }
+
+        // Progress to the next level under the health bar
+        let xp_bar_pos_px = health_bar_pos_px + Vector::new(0, tile_size_px.y);
+        let (previous_xp, next_xp) = (
+            experience::next_level_xp(player.level - 1).unwrap_or(0),
+            experience::next_level_xp(player.level),
+        );
+        let xp_fraction = match next_xp {
+            Some(next) => (player.xp - previous_xp) as f32 / (next - previous_xp) as f32,
+            None => 1.0,
+        };
+        window.draw(
+            &Rectangle::new(xp_bar_pos_px, (full_health_width_px, tile_size_px.y / 2.0)),
+            Col(Color::BLUE.with_alpha(0.5)),
+        );
+        window.draw(
+            &Rectangle::new(
+                xp_bar_pos_px,
+                (xp_fraction * full_health_width_px, tile_size_px.y / 2.0),
+            ),
+            Col(Color::BLUE),
+        );
+
+        // Mana under the xp bar, for players who have any
+        let mana_bar_pos_px = xp_bar_pos_px + Vector::new(0.0, tile_size_px.y / 2.0);
+        if player.max_mana > 0 {
+            let mana_fraction = player.mana as f32 / player.max_mana as f32;
+            window.draw(
+                &Rectangle::new(
+                    mana_bar_pos_px,
+                    (full_health_width_px, tile_size_px.y / 2.0),
+                ),
+                Col(Color::CYAN.with_alpha(0.5)),
+            );
+            window.draw(
+                &Rectangle::new(
+                    mana_bar_pos_px,
+                    (mana_fraction * full_health_width_px, tile_size_px.y / 2.0),
+                ),
+                Col(Color::CYAN),
+            );
+        }
+
+        // A coloured icon and label for each status effect, under the mana
+        // bar, then how hungry the player is
+        let status_row_px = health_bar_pos_px + Vector::new(0.0, tile_size_px.y * 2.0);
+        let statuses = player.statuses.effects;
+        let hunger = player.hunger().info();
+        self.font.execute(|font| {
+            let mut pos_px = status_row_px;
+            for status in statuses.iter().flatten() {
+                let info = status.kind.info();
+                window.draw(
+                    &Rectangle::new(pos_px, tile_size_px.times((0.5, 1.0))),
+                    Col(info.color),
+                );
+                let label = format!("{} {}", info.label, status.turns);
+                let image = font.render(&label, &FontStyle::new(16.0, Color::BLACK))?;
+                let label_px = pos_px + Vector::new(tile_size_px.x * 0.75, 0.0);
+                window.draw(&image.area().translate(label_px), Img(&image));
+                pos_px.x += tile_size_px.x * 0.75 + image.area().width() + 10.0;
+            }
+            if !hunger.label.is_empty() {
+                let image = font.render(hunger.label, &FontStyle::new(16.0, hunger.color))?;
+                window.draw(&image.area().translate(pos_px), Img(&image));
+            }
+            Ok(())
+        })?;
+
+        let mut inventory = level_text(player);
+        inventory.push_str(&format!("\n{}", stats_text(player)));
+        inventory.push_str(&format!("\n{}", objective_text(&self.dungeon)));
+        let spells = spell_text(player, self.casting);
+        if !spells.is_empty() {
+            inventory.push_str(&format!("\n{}", spells));
+        }
+        if self.using_item {
+            inventory.push_str(&format!("\n{}", use_text(&self.dungeon)));
+        }
+        let trade = shop_text(&self.dungeon, self.shopping);
+        if !trade.is_empty() {
+            inventory.push_str(&format!("\n{}", trade));
+        }
+        inventory.push_str(&format!("\n{}", inventory_text(&self.dungeon)));
+        self.font.execute(|font| {
+            let image = font.render(&inventory, &FontStyle::new(20.0, Color::BLACK))?;
             window.draw(
                 &image
                     .area()
-                    .translate(health_bar_pos_px + Vector::new(0, tile_size_px.y)),
+                    .translate(health_bar_pos_px + Vector::new(0, tile_size_px.y * 3.0)),
                 Img(&image),
             );
             Ok(())
         })?;
 
+        // Message log under the map
+        if !self.messages.is_empty() {
+            let log = self.messages.join("\n");
+            let log_pos_px = offset_px + Vector::new(0.0, map_size_px.y + tile_size_px.y);
+            self.font.execute(|font| {
+                let image = font.render(&log, &FontStyle::new(20.0, Color::BLACK))?;
+                window.draw(&image.area().translate(log_pos_px), Img(&image));
+                Ok(())
+            })?;
+        }
+
         Ok(())
     }
 }
@@ -1,14 +1,51 @@
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::cmp;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+pub mod ability;
+pub mod ascii_map;
+pub mod boss;
+pub mod character;
+pub mod consumable;
+pub mod equipment;
+pub mod event;
+pub mod experience;
+pub mod flee;
+pub mod hunger;
+pub mod item;
+pub mod loot;
 pub mod monster;
+pub mod movement;
+pub mod pack;
+pub mod perception;
 pub mod player;
+pub mod quest;
+pub mod rest;
+pub mod shop;
+pub mod spawn;
+pub mod spell;
+pub mod status;
+pub mod terrain;
+pub mod trap;
+pub mod vault;
+use crate::dungeon::event::Event;
+use crate::dungeon::item::{Item, ItemKind};
 use crate::dungeon::monster::*;
 use crate::dungeon::player::Player;
+use crate::dungeon::quest::{Quest, RunStats, DEFAULT_QUEST};
+use crate::dungeon::shop::Shop;
+use crate::dungeon::spawn::{SpawnRules, DEFAULT_SPAWN_RULES};
+use crate::dungeon::status::{StatusKind, Statuses};
+use crate::dungeon::terrain::{Terrain, IMPASSABLE};
+use crate::dungeon::trap::{Trap, MAX_TRAPS};
+use crate::dungeon::vault::Vault;
 use colored::Colorize;
 use quicksilver::prelude::Color;
 
 #[derive(Copy, Clone)]
 pub struct Dungeon {
+    pub seed: u64,
     pub dungeon_x: usize,
     pub dungeon_y: usize,
     pub min_width: usize,
@@ -16,101 +53,192 @@ pub struct Dungeon {
     pub min_length: usize,
     pub max_length: usize,
     pub rooms: usize,
-    pub char_map: [[char; 80]; 30],
+    /// How many levels down this one is, counting from 1.
+    pub depth: u32,
+    pub quest: Quest,
+    /// Whether the player has done what the quest asks.
+    pub won: bool,
+    pub stats: RunStats,
+    pub terrain_map: [[Terrain; 80]; 30],
     pub player: Player,
     pub hardness_map: [[u8; 80]; 30],
     pub distance_map: [[u16; 80]; 30],
+    pub tunnel_map: [[u16; 80]; 30],
+    /// Where fleeing monsters that open doors run to, see `flee::safety_map`.
+    pub safety_map: [[i32; 80]; 30],
     pub mon_map: [[bool; 80]; 30],
     pub monsters: [Monster; 10],
+    /// How loud the player was this turn, heard by monsters on their move.
+    pub noise: u16,
+    /// Turns the player has taken in the game.
+    pub turn: u32,
+    pub items: [Option<Item>; 40],
+    pub traps: [Option<Trap>; MAX_TRAPS],
+    /// Which item kinds the player has identified, by `ItemKind` order. What
+    /// unidentified ones look like follows from `seed`.
+    pub identified: [bool; ItemKind::ALL.len()],
+    /// Whether magic mapping has shown the player every monster.
+    pub mapped: bool,
+    pub shop: Option<Shop>,
+    pub events: [Option<Event>; 16],
+    /// The depth the player is on their way to, by stairs or a pit, until
+    /// `Levels::travel` takes them there.
+    pub leaving_for: Option<u32>,
 }
 
 impl Dungeon {
     pub fn print(self) {
         for i in 0..self.dungeon_x {
             for j in 0..self.dungeon_y {
-                if self.char_map[i][j] == '.' {
-                    print!("{}", self.char_map[i][j].to_string().green());
-                } else if self.char_map[i][j] == '#' {
-                    print!("{}", self.char_map[i][j].to_string().yellow());
-                } else if self.char_map[i][j] == '@' {
-                    print!("{}", self.char_map[i][j].to_string().red());
+                if self.player.position_x == i && self.player.position_y == j {
+                    print!("{}", self.player.character.to_string().red());
                 } else {
-                    print!("{}", self.char_map[i][j].to_string().white());
+                    let terrain = self.terrain_map[i][j];
+                    print!("{}", terrain.glyph().to_string().color(terrain.term_color()));
                 }
             }
-            println!("");
+            println!();
         }
     }
 
     pub fn print_distance_map(self) {
         for i in 0..self.dungeon_x {
             for j in 0..self.dungeon_y {
-                if self.distance_map[i][j] != 1055 {
+                if self.distance_map[i][j] != IMPASSABLE {
                     print!("{}", self.distance_map[i][j] % 10);
                 } else {
                     print!(" ");
                 }
             }
-            println!("");
+            println!();
         }
     }
 
-    pub fn move_character(self, direction: char, value: usize) -> bool {
+    /// The cell next to the player in the given direction.
+    pub fn target_cell(self, direction: char, value: usize) -> (usize, usize) {
         if direction == 'x' {
             if value == 1000 {
-                if self.hardness_map[self.player.position_x][self.player.position_y - 1] > 2
-                    || self.mon_map[self.player.position_x][self.player.position_y - 1]
-                {
-                    return false;
-                }
+                (self.player.position_x, self.player.position_y - 1)
             } else {
-                if self.hardness_map[self.player.position_x][self.player.position_y + 1] > 2
-                    || self.mon_map[self.player.position_x][self.player.position_y + 1]
-                {
-                    return false;
-                }
+                (self.player.position_x, self.player.position_y + 1)
             }
+        } else if value == 1000 {
+            (self.player.position_x - 1, self.player.position_y)
         } else {
-            if value == 1000 {
-                if self.hardness_map[self.player.position_x - 1][self.player.position_y] > 2
-                    || self.mon_map[self.player.position_x - 1][self.player.position_y]
-                {
-                    return false;
-                }
-            } else {
-                if self.hardness_map[self.player.position_x + 1][self.player.position_y] > 2
-                    || self.mon_map[self.player.position_x + 1][self.player.position_y]
-                {
-                    return false;
-                }
-            }
+            (self.player.position_x + 1, self.player.position_y)
         }
+    }
+
+    pub fn move_character(self, direction: char, value: usize) -> bool {
+        let (x, y) = self.target_cell(direction, value);
+        self.terrain_map[x][y].passable() && !self.mon_map[x][y]
+    }
+
+    /// Whether a monster could step into (or open its way into) a cell.
+    pub fn monster_can_enter(self, monster: usize, x: usize, y: usize) -> bool {
+        let terrain = self.terrain_map[x][y];
+        if terrain.passable() {
+            return true;
+        }
+        let monster = self.monsters[monster];
+        if terrain.diggable() {
+            return monster.tunnels();
+        }
+        terrain.opens_to().is_some() && !terrain.needs_key() && monster.opens_doors()
+    }
 
-        true
+    /// Every cell in between two cells must be transparent.
+    pub fn line_of_sight(self, from: (usize, usize), to: (usize, usize)) -> bool {
+        let cells = line(from, to);
+        cells[..cells.len().saturating_sub(1)]
+            .iter()
+            .all(|&(x, y)| self.terrain_map[x][y].transparent())
     }
+
+    /// The cell a monster would like to step into this turn, ignoring the
+    /// other monsters (they may move out of the way). Monsters head for where
+    /// they last saw or heard the player, and stay put when they have no idea
+    /// or no neighbour is any closer. Frightened monsters that know where the
+    /// player is run down the safety map instead, and pack followers may have
+    /// a place of their own to be (see `pack::pack_goal`). Confused monsters
+    /// just stumble about.
     pub fn determine_monster_move(self, monster: usize) -> (usize, usize) {
         let posx = self.monsters[monster].pos_x;
         let posy = self.monsters[monster].pos_y;
-        let mut x_min: usize = 0;
-        let mut y_min: usize = 0;
-        let mut min = 10000;
-        for x in (posx - 1)..(posx + 2) {
-            for y in (posy - 1)..(posy + 2) {
-                if !self.mon_map[x][y] {
-                    if self.distance_map[x][y] < min {
-                        min = self.distance_map[x][y];
-                        x_min = x;
-                        y_min = y;
-                    }
+        let tunnels = self.monsters[monster].tunnels();
+        if self.monsters[monster].statuses.has(StatusKind::Confusion) {
+            return self.stumble(monster);
+        }
+        if self.monsters[monster].fleeing && self.monsters[monster].last_seen.is_some() {
+            if self.monsters[monster].opens_doors() {
+                return self.downhill(monster, &self.safety_map);
+            }
+            return self.downhill(monster, &flee::safety_map(&self, false));
+        }
+        if let Some(goal) = pack::pack_goal(&self, monster) {
+            // Walk around the player and the rest of the pack, not through
+            let mut around = self;
+            around.terrain_map[self.player.position_x][self.player.position_y] = Terrain::Wall;
+            for (i, other) in self.monsters.iter().enumerate() {
+                if i != monster && other.alive {
+                    around.terrain_map[other.pos_x][other.pos_y] = Terrain::Wall;
+                }
+            }
+            return self.downhill(monster, &goal_map(&around, goal, tunnels));
+        }
+        let map = match self.monsters[monster].last_seen {
+            None => return (posx, posy),
+            Some(goal) if goal == (self.player.position_x, self.player.position_y) => {
+                if tunnels {
+                    self.tunnel_map
+                } else {
+                    self.distance_map
                 }
             }
+            Some(goal) => goal_map(&self, goal, tunnels),
+        };
+        self.downhill(monster, &map)
+    }
+
+    /// A random cell next to a confused monster that it could step into,
+    /// the same one for the same level, turn and monster.
+    fn stumble(self, monster: usize) -> (usize, usize) {
+        let (posx, posy) = (self.monsters[monster].pos_x, self.monsters[monster].pos_y);
+        let player = (self.player.position_x, self.player.position_y);
+        let cells: Vec<(usize, usize)> = neighbourhood(posx, posy)
+            .filter(|&(x, y)| (x, y) != player && self.monster_can_enter(monster, x, y))
+            .collect();
+        if cells.is_empty() {
+            return (posx, posy);
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed ^ ((self.turn as u64) << 8) ^ monster as u64);
+        cells[rng.gen_range(0..cells.len())]
+    }
+
+    /// The neighbour of a monster with the lowest value on a map, or where it
+    /// stands if none is lower.
+    fn downhill<T: Copy + PartialOrd>(self, monster: usize, map: &[[T; 80]; 30]) -> (usize, usize) {
+        let posx = self.monsters[monster].pos_x;
+        let posy = self.monsters[monster].pos_y;
+        let (mut x_min, mut y_min) = (posx, posy);
+        let mut min = map[posx][posy];
+        for (x, y) in neighbourhood(posx, posy) {
+            let is_player = x == self.player.position_x && y == self.player.position_y;
+            if !is_player && self.monster_can_enter(monster, x, y) && map[x][y] < min {
+                min = map[x][y];
+                x_min = x;
+                y_min = y;
+            }
         }
         (x_min, y_min)
     }
 }
 
-pub fn generate_dungeon() -> Dungeon {
-    let mut dungeon = Dungeon {
+/// A dungeon of solid rock with the starting player and monster roster,
+/// ready for a level to be carved or loaded into it.
+fn empty_dungeon() -> Dungeon {
+    Dungeon {
+        seed: 0,
         dungeon_x: 30,
         dungeon_y: 80,
         min_width: 4,
@@ -118,9 +246,18 @@ pub fn generate_dungeon() -> Dungeon {
         min_length: 4,
         max_length: 12,
         rooms: 7,
-        char_map: [[' '; 80]; 30],
+        depth: 1,
+        quest: DEFAULT_QUEST,
+        won: false,
+        stats: RunStats {
+            deepest_reached: 1,
+            ..RunStats::default()
+        },
+        terrain_map: [[Terrain::Rock; 80]; 30],
         player: Player {
             character: '@',
+            race: "",
+            class: "",
             position_x: 1000,
             position_y: 1000,
             hp: 100,
@@ -128,66 +265,387 @@ pub fn generate_dungeon() -> Dungeon {
             attack: 10,
             defence: 10,
             alive: true,
+            level: 1,
+            xp: 0,
+            perks: 0,
+            regeneration: 3,
+            regen_progress: 0,
+            nutrition: hunger::STARTING_NUTRITION,
+            mana: 0,
+            max_mana: 0,
+            mana_progress: 0,
+            spells: [None; spell::MAX_SPELLS],
             color: Color::RED,
-            target: (100, 100)
+            target: (100, 100),
+            statuses: Statuses::default(),
+            talents: [None; character::MAX_TALENTS],
+            gold: 0,
+            inventory: [
+                Some(ItemKind::Darts),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            ],
+            equipment: [
+                Some(ItemKind::Sword),
+                Some(ItemKind::Shield),
+                None,
+                None,
+                None,
+                None,
+            ],
         },
         hardness_map: [[255; 80]; 30],
         distance_map: [[0; 80]; 30],
+        tunnel_map: [[0; 80]; 30],
+        safety_map: [[0; 80]; 30],
         mon_map: [[false; 80]; 30],
-        monsters: [Monster {
-            character: 'g',
-            pos_x: 100,
-            pos_y: 100,
-            hp: 10,
-            max_hp: 10,
-            attack: 1,
-            defence: 1,
-            alive: true,
-            color: Color::BLUE
-        }; 10],
+        monsters: [Monster::new(MonsterKind::Goblin); 10],
+        noise: 0,
+        turn: 0,
+        items: [None; 40],
+        traps: [None; MAX_TRAPS],
+        identified: [false; ItemKind::ALL.len()],
+        mapped: false,
+        shop: None,
+        events: [None; 16],
+        leaving_for: None,
+    }
+}
+
+/// The first level of a new game with a random seed.
+pub fn generate_dungeon(quest: Quest) -> Dungeon {
+    generate_level(rand::thread_rng().gen(), 1, quest, &DEFAULT_SPAWN_RULES)
+}
+
+/// Food left lying around on every level.
+const RATIONS_PER_LEVEL: usize = 2;
+
+/// Chance that a plain room has a pool of water in it.
+const POOL_CHANCE: f64 = 0.3;
+
+/// Generate the first level; the same seed and rules always give the same
+/// level.
+pub fn generate_dungeon_with(seed: u64, rules: &SpawnRules) -> Dungeon {
+    generate_level(seed, 1, DEFAULT_QUEST, rules)
+}
+
+/// Mixed with the depth into the game's seed for the levels below the first.
+const LEVEL_SALT: u64 = 0x1e7e1;
+
+/// Mixed into the level's seed, with the attempt, when a level has to be
+/// laid out again.
+const RETRY_SALT: u64 = 0x2e7;
+
+/// Most times a level is laid out to keep the boss away from the player.
+const MAX_LEVEL_ATTEMPTS: u64 = 100;
+
+/// Generate the level at a depth of the game with the given seed. Every level
+/// but the deepest has stairs down, and the player starts on the stairs up.
+/// The deepest level is laid out again until the boss fits on it out of the
+/// player's way, and if it never does, the boss sleeps in whichever room
+/// cell is furthest from them.
+pub fn generate_level(seed: u64, depth: u32, quest: Quest, rules: &SpawnRules) -> Dungeon {
+    let level_seed = match depth {
+        1 => seed,
+        _ => seed ^ LEVEL_SALT.wrapping_mul(depth as u64),
     };
+    let distance = rules.min_player_distance;
+    for attempt in 0..MAX_LEVEL_ATTEMPTS {
+        let attempt_seed = level_seed ^ RETRY_SALT.wrapping_mul(attempt);
+        let (d, placed) = build_level(seed, attempt_seed, depth, quest, rules, distance);
+        if placed {
+            return d;
+        }
+    }
+    build_level(seed, level_seed, depth, quest, rules, 0).0
+}
+
+/// Lay out one level from its own seed, keeping the boss at least
+/// `boss_distance` from the player. Returns whether the boss was placed,
+/// which it always is off the deepest level.
+fn build_level(
+    seed: u64,
+    level_seed: u64,
+    depth: u32,
+    quest: Quest,
+    rules: &SpawnRules,
+    boss_distance: u16,
+) -> (Dungeon, bool) {
+    let mut rng = StdRng::seed_from_u64(level_seed);
+    let mut dungeon = empty_dungeon();
+    dungeon.seed = seed;
+    dungeon.depth = depth;
+    dungeon.quest = quest;
+    dungeon = add_rock_hardness(dungeon, &mut rng);
 
+    let vaults = vault::library();
     let mut rooms = 0;
     let mut room_list: Vec<(usize, usize, usize, usize)> = Vec::new();
+    let mut vault_list: Vec<(usize, usize, Vault)> = Vec::new();
 
     while rooms < dungeon.rooms {
-        let length = rand::thread_rng().gen_range(dungeon.min_length..dungeon.max_length);
-        let width = rand::thread_rng().gen_range(dungeon.min_width..dungeon.max_width);
+        // Now and then try a prefab vault instead of a plain room
+        let vault = if rng.gen_range(0..4) == 0 {
+            let template = &vaults[rng.gen_range(0..vaults.len())];
+            Some(template.random_orientation(&mut rng))
+        } else {
+            None
+        };
+        let (width, length) = match &vault {
+            Some(v) => (v.height(), v.width()),
+            None => (
+                rng.gen_range(dungeon.min_width..dungeon.max_width),
+                rng.gen_range(dungeon.min_length..dungeon.max_length),
+            ),
+        };
+        if width + 2 > dungeon.dungeon_x || length + 2 > dungeon.dungeon_y {
+            continue;
+        }
 
         let max_width = dungeon.dungeon_x - width;
         let max_length = dungeon.dungeon_y - length;
 
-        let x = rand::thread_rng().gen_range(1..max_width);
-        let y = rand::thread_rng().gen_range(1..max_length);
+        let x = rng.gen_range(1..max_width);
+        let y = rng.gen_range(1..max_length);
 
         if is_valid_room(x, y, width, length, &dungeon) {
             dungeon = add_room(x, y, width, length, dungeon);
             rooms += 1;
             room_list.push((x, y, length, width));
+            if let Some(v) = vault {
+                vault_list.push((x, y, v));
+            }
         }
     }
-    dungeon = add_hallways(dungeon, room_list);
+    // Vaults are laid out as plain rooms so the hallways reach them, then
+    // stamped over whatever the hallways carved through them
+    dungeon = add_hallways(dungeon, room_list.clone());
+    dungeon = add_border(dungeon);
+    for (x, y, v) in vault_list.iter() {
+        dungeon = vault::stamp(dungeon, *x, *y, v);
+    }
+    dungeon = add_doors(dungeon, &mut rng);
+    let plain_rooms: Vec<_> = room_list
+        .iter()
+        .copied()
+        .filter(|&(x, y, _, _)| !vault_list.iter().any(|(vx, vy, _)| (*vx, *vy) == (x, y)))
+        .collect();
+    dungeon = add_pools(dungeon, &plain_rooms, &mut rng);
 
     let mut set_player: bool = false;
     while !set_player {
-        let x = rand::thread_rng().gen_range(1..dungeon.dungeon_x);
-        let y = rand::thread_rng().gen_range(1..dungeon.dungeon_y);
+        let x = rng.gen_range(1..dungeon.dungeon_x);
+        let y = rng.gen_range(1..dungeon.dungeon_y);
 
-        if dungeon.char_map[x][y] == '.' {
+        let in_vault = vault_list.iter().any(|(vx, vy, v)| {
+            (*vx..vx + v.height()).contains(&x) && (*vy..vy + v.width()).contains(&y)
+        });
+        // Keep clear of monsters the vaults brought with them
+        let near_monster = dungeon.monsters.iter().any(|m| {
+            m.pos_x != UNPLACED
+                && m.pos_x.abs_diff(x).max(m.pos_y.abs_diff(y)) < rules.min_player_distance as usize
+        });
+        if dungeon.terrain_map[x][y] == Terrain::Floor && !in_vault && !near_monster {
             dungeon.player.position_x = x;
             dungeon.player.position_y = y;
             set_player = true;
         }
     }
+    let start = (dungeon.player.position_x, dungeon.player.position_y);
+    dungeon = set_terrain(dungeon, start.0, start.1, Terrain::UpStairs);
+    dungeon = place_keys(dungeon, &mut rng);
+    dungeon = place_reachable_item(dungeon, ItemKind::Pick, &mut rng);
+    for _ in 0..RATIONS_PER_LEVEL {
+        dungeon = place_reachable_item(dungeon, ItemKind::Ration, &mut rng);
+    }
+    if let Some(floor) = loot::table(loot::tables(), "floor") {
+        for kind in floor.roll(dungeon.depth, &mut rng) {
+            dungeon = place_reachable_item(dungeon, kind, &mut rng);
+        }
+    }
     dungeon = calculate_distance_map(dungeon);
-    dungeon = place_monsters(dungeon);
-    dungeon
+    dungeon = place_down_stairs(dungeon);
+    dungeon = quest::place_amulet(dungeon);
+    // The boss keeps to the deepest level, and comes before the shopkeeper
+    // and the rest
+    let mut placed = true;
+    if quest::is_deepest(&dungeon) {
+        (dungeon, placed) = boss::place_boss(dungeon, &plain_rooms, boss_distance);
+    }
+    let room_ids = spawn::room_ids(&dungeon);
+    let boss_room = boss::find_boss(&dungeon).map(|b| {
+        let boss = dungeon.monsters[b];
+        room_ids[boss.pos_x][boss.pos_y]
+    });
+    // One plain room away from the player is filled with treasure
+    let player = (dungeon.player.position_x, dungeon.player.position_y);
+    let treasure_rooms: Vec<_> = plain_rooms
+        .iter()
+        .copied()
+        .filter(|&(x, y, length, width)| {
+            !((x..x + width).contains(&player.0) && (y..y + length).contains(&player.1))
+        })
+        .collect();
+    if !treasure_rooms.is_empty() {
+        let room = treasure_rooms[rng.gen_range(0..treasure_rooms.len())];
+        dungeon = loot::fill_room(dungeon, "treasure", room, &mut rng);
+        // Another one becomes a shop, which the boss keeps out of
+        let shop_rooms: Vec<_> = treasure_rooms
+            .into_iter()
+            .filter(|&(x, y, length, width)| {
+                let boss_here = (x..x + width)
+                    .any(|cx| (y..y + length).any(|cy| Some(room_ids[cx][cy]) == boss_room));
+                (x, y, length, width) != room && !boss_here
+            })
+            .collect();
+        dungeon = shop::place_shop(dungeon, &shop_rooms, rules.min_player_distance, &mut rng).0;
+    }
+    dungeon = spawn::place_monsters(dungeon, rules, &mut rng);
+    dungeon = trap::place_traps(dungeon, &mut rng);
+    (dungeon, placed)
+}
+
+/// The floor cell with no item on it that is the longest walk from the
+/// player.
+pub fn furthest_free_floor(d: &Dungeon) -> Option<(usize, usize)> {
+    (1..d.dungeon_x)
+        .flat_map(|x| (1..d.dungeon_y).map(move |y| (x, y)))
+        .filter(|&(x, y)| {
+            d.terrain_map[x][y] == Terrain::Floor
+                && d.distance_map[x][y] < IMPASSABLE
+                && !d
+                    .items
+                    .iter()
+                    .flatten()
+                    .any(|i| (i.pos_x, i.pos_y) == (x, y))
+        })
+        .max_by_key(|&(x, y)| d.distance_map[x][y])
+}
+
+/// Put the stairs down as far from the player as they go, unless the level
+/// is the deepest.
+fn place_down_stairs(d: Dungeon) -> Dungeon {
+    if quest::is_deepest(&d) {
+        return d;
+    }
+    match furthest_free_floor(&d) {
+        Some((x, y)) => set_terrain(d, x, y, Terrain::Stairs),
+        None => d,
+    }
+}
+
+/// The levels the player has left, each kept as it was when they left it so
+/// that what they killed or took stays gone. A `Dungeon` is one level and
+/// passes by value, so the rest are held here by whoever runs the game.
+#[derive(Default)]
+pub struct Levels {
+    left: HashMap<u32, Dungeon>,
+}
+
+impl Levels {
+    /// Take the player to the depth they are leaving for, if any: the level
+    /// as they left it, or a new one the first time. They arrive on its
+    /// stairs back the way they came, keeping everything but their place, and
+    /// so does what they know of the items and the run so far.
+    pub fn travel(&mut self, d: Dungeon) -> Dungeon {
+        let Some(depth) = d.leaving_for else {
+            return d;
+        };
+        let mut left = d;
+        left.leaving_for = None;
+        self.left.insert(d.depth, left);
+        let next = match self.left.remove(&depth) {
+            Some(next) => next,
+            None => generate_level(d.seed, depth, d.quest, &DEFAULT_SPAWN_RULES),
+        };
+        arrive(&d, next)
+    }
+}
+
+/// Bring the player, and what they know of the items and the run, over from
+/// `d` onto the level they are arriving on.
+fn arrive(d: &Dungeon, mut next: Dungeon) -> Dungeon {
+    let stairs = if next.depth > d.depth {
+        Terrain::UpStairs
+    } else {
+        Terrain::Stairs
+    };
+    let (x, y) = landing(&next, stairs);
+    next.player = d.player;
+    next.player.position_x = x;
+    next.player.position_y = y;
+    next.identified = d.identified;
+    next.turn = d.turn;
+    next.won = d.won;
+    next.stats = d.stats;
+    next.stats.deepest_reached = next.stats.deepest_reached.max(next.depth);
+    next.events = d.events;
+    next = calculate_distance_map(monster_map(next));
+    let depth = next.depth;
+    if depth > d.depth {
+        push_event(next, Event::Descended { depth })
+    } else {
+        push_event(next, Event::Ascended { depth })
+    }
+}
+
+/// Where the player arrives on a level: its stairs of the given kind, or the
+/// first open cell beside them if a monster is standing there.
+fn landing(d: &Dungeon, stairs: Terrain) -> (usize, usize) {
+    let start = (d.player.position_x, d.player.position_y);
+    let stairs = (0..d.dungeon_x)
+        .flat_map(|x| (0..d.dungeon_y).map(move |y| (x, y)))
+        .find(|&(x, y)| d.terrain_map[x][y] == stairs)
+        .unwrap_or(start);
+    let occupied = |(x, y): (usize, usize)| {
+        d.monsters
+            .iter()
+            .any(|m| m.alive && (m.pos_x, m.pos_y) == (x, y))
+    };
+    std::iter::once(stairs)
+        .chain(neighbourhood(stairs.0, stairs.1))
+        .find(|&(x, y)| d.terrain_map[x][y].passable() && !occupied((x, y)))
+        .unwrap_or(stairs)
+}
+
+/// Send the player down to the next level, which `Levels::travel` takes
+/// them to once the turn ends.
+pub fn descend(mut d: Dungeon) -> Dungeon {
+    d.leaving_for = Some(d.depth + 1);
+    d
+}
+
+/// Send the player up to the level above, to arrive on its stairs down.
+pub fn ascend(mut d: Dungeon) -> Dungeon {
+    d.leaving_for = Some(d.depth - 1);
+    d
+}
+
+/// Take the stairs the player stands on, up out of the dungeon from the
+/// first level. Returns whether the player went anywhere.
+pub fn take_stairs(d: Dungeon) -> (Dungeon, bool) {
+    if !d.player.alive || d.won {
+        return (d, false);
+    }
+    match d.terrain_map[d.player.position_x][d.player.position_y] {
+        Terrain::Stairs => (descend(d), true),
+        Terrain::UpStairs if d.depth == 1 => quest::reach_surface(d),
+        Terrain::UpStairs => (ascend(d), true),
+        _ => (d, false),
+    }
 }
 
 fn is_valid_room(x: usize, y: usize, width: usize, length: usize, d: &Dungeon) -> bool {
     for i in x..(x + width) {
         for j in y..(y + length) {
-            if d.char_map[i][j] != ' ' {
+            if d.terrain_map[i][j] != Terrain::Rock {
                 return false;
             }
         }
@@ -198,13 +656,267 @@ fn is_valid_room(x: usize, y: usize, width: usize, length: usize, d: &Dungeon) -
 fn add_room(x: usize, y: usize, width: usize, length: usize, mut d: Dungeon) -> Dungeon {
     for i in x..(x + width) {
         for j in y..(y + length) {
-            d.char_map[i][j] = '.';
-            d.hardness_map[i][j] = 0;
+            d = set_terrain(d, i, j, Terrain::Floor);
+        }
+    }
+    d
+}
+
+fn add_border(mut d: Dungeon) -> Dungeon {
+    for x in 0..d.dungeon_x {
+        for y in 0..d.dungeon_y {
+            if x == 0 || x == d.dungeon_x - 1 || y == 0 || y == d.dungeon_y - 1 {
+                d = set_terrain(d, x, y, Terrain::Wall);
+            }
+        }
+    }
+    d
+}
+
+/// Put a door on every corridor cell that leads straight into a room.
+fn add_doors(mut d: Dungeon, rng: &mut StdRng) -> Dungeon {
+    for x in 1..(d.dungeon_x - 1) {
+        for y in 1..(d.dungeon_y - 1) {
+            if d.terrain_map[x][y] == Terrain::Corridor && is_doorway(&d, x, y) {
+                let roll = rng.gen_range(0..10);
+                let door = if roll < 2 {
+                    Terrain::LockedDoor
+                } else if roll < 7 {
+                    Terrain::ClosedDoor
+                } else {
+                    Terrain::OpenDoor
+                };
+                d = set_terrain(d, x, y, door);
+            }
+        }
+    }
+    d
+}
+
+/// Flood the middle of some plain rooms, leaving a ring of floor around each
+/// pool so the way across the room is never cut off.
+fn add_pools(mut d: Dungeon, rooms: &[(usize, usize, usize, usize)], rng: &mut StdRng) -> Dungeon {
+    for &(x, y, length, width) in rooms {
+        if width < 3 || length < 3 || !rng.gen_bool(POOL_CHANCE) {
+            continue;
+        }
+        let height = rng.gen_range(1..=width - 2);
+        let breadth = rng.gen_range(1..=length - 2);
+        let top = rng.gen_range(x + 1..=x + width - 1 - height);
+        let left = rng.gen_range(y + 1..=y + length - 1 - breadth);
+        for i in top..top + height {
+            for j in left..left + breadth {
+                // Hallways may run through the room, leave them dry
+                if d.terrain_map[i][j] == Terrain::Floor {
+                    d = set_terrain(d, i, j, Terrain::Water);
+                }
+            }
+        }
+    }
+    d
+}
+
+/// A doorway has a room on one side, the corridor on the opposite side and
+/// solid rock on the other two.
+fn is_doorway(d: &Dungeon, x: usize, y: usize) -> bool {
+    let floor = |x: usize, y: usize| d.terrain_map[x][y] == Terrain::Floor;
+    let corridor = |x: usize, y: usize| d.terrain_map[x][y] == Terrain::Corridor;
+    let solid = |x: usize, y: usize| !d.terrain_map[x][y].passable();
+
+    let vertical = solid(x, y - 1)
+        && solid(x, y + 1)
+        && ((floor(x - 1, y) && corridor(x + 1, y)) || (corridor(x - 1, y) && floor(x + 1, y)));
+    let horizontal = solid(x - 1, y)
+        && solid(x + 1, y)
+        && ((floor(x, y - 1) && corridor(x, y + 1)) || (corridor(x, y - 1) && floor(x, y + 1)));
+    vertical || horizontal
+}
+
+/// Cells the player can reach from the start without going through a locked door.
+fn reachable_from_player(d: &Dungeon) -> [[bool; 80]; 30] {
+    let mut seen = [[false; 80]; 30];
+    let mut stack = vec![(d.player.position_x, d.player.position_y)];
+    seen[d.player.position_x][d.player.position_y] = true;
+
+    while let Some((x, y)) = stack.pop() {
+        for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            let terrain = d.terrain_map[nx][ny];
+            let walkable = terrain.passable() || (terrain.opens_to().is_some() && !terrain.needs_key());
+            if walkable && !seen[nx][ny] {
+                seen[nx][ny] = true;
+                stack.push((nx, ny));
+            }
+        }
+    }
+    seen
+}
+
+/// Drop one key for every locked door somewhere the player can get to first.
+fn place_keys(mut d: Dungeon, rng: &mut StdRng) -> Dungeon {
+    for x in 0..d.dungeon_x {
+        for y in 0..d.dungeon_y {
+            if d.terrain_map[x][y] == Terrain::LockedDoor {
+                d = place_reachable_item(d, ItemKind::Key, rng);
+            }
+        }
+    }
+    d
+}
+
+/// Drop an item on a random floor cell the player can walk to from the start.
+fn place_reachable_item(d: Dungeon, kind: ItemKind, rng: &mut StdRng) -> Dungeon {
+    let reachable = reachable_from_player(&d);
+    loop {
+        let x = rng.gen_range(1..d.dungeon_x);
+        let y = rng.gen_range(1..d.dungeon_y);
+
+        let is_player = x == d.player.position_x && y == d.player.position_y;
+        if reachable[x][y] && d.terrain_map[x][y] == Terrain::Floor && !is_player {
+            return drop_item(d, kind, x, y).0;
+        }
+    }
+}
+
+/// A random floor cell with nobody standing on it, if there is one. Teleport
+/// traps are left out, so that teleporting never lands on one and goes round
+/// in circles.
+pub fn free_floor_cell(d: &Dungeon, rng: &mut StdRng) -> Option<(usize, usize)> {
+    let cells: Vec<(usize, usize)> = (1..d.dungeon_x)
+        .flat_map(|x| (1..d.dungeon_y).map(move |y| (x, y)))
+        .filter(|&(x, y)| {
+            let taken = (d.player.position_x, d.player.position_y) == (x, y)
+                || d.monsters
+                    .iter()
+                    .any(|m| m.alive && (m.pos_x, m.pos_y) == (x, y));
+            let teleport = trap::trap_at(d, x, y)
+                .and_then(|slot| d.traps[slot])
+                .is_some_and(|t| t.kind == trap::TrapKind::Teleport);
+            d.terrain_map[x][y] == Terrain::Floor && !taken && !teleport
+        })
+        .collect();
+    if cells.is_empty() {
+        return None;
+    }
+    Some(cells[rng.gen_range(0..cells.len())])
+}
+
+/// Put an item on the floor, returns false when there is no free item slot.
+pub fn drop_item(mut d: Dungeon, kind: ItemKind, x: usize, y: usize) -> (Dungeon, bool) {
+    match d.items.iter_mut().find(|slot| slot.is_none()) {
+        Some(slot) => {
+            *slot = Some(Item {
+                kind,
+                pos_x: x,
+                pos_y: y,
+            });
+            (d, true)
+        }
+        None => (d, false),
+    }
+}
+
+/// Move any items under the player into the inventory, and gold into their
+/// purse. Goods picked up in a shop are owed for.
+pub fn pick_up_items(mut d: Dungeon) -> Dungeon {
+    let (x, y) = (d.player.position_x, d.player.position_y);
+    for i in 0..d.items.len() {
+        if let Some(item) = d.items[i] {
+            if item.pos_x != x || item.pos_y != y {
+                continue;
+            }
+            if item.kind == ItemKind::Gold {
+                d.items[i] = None;
+                d = shop::pick_up_gold(d);
+            } else if d.player.add_item(item.kind) {
+                d.items[i] = None;
+                d = shop::picked_up(d, item.kind, x, y);
+            }
+        }
+    }
+    d
+}
+
+/// Open the door at a cell, using up a key if it is locked.
+/// Returns whether a door was opened.
+pub fn open_door(mut d: Dungeon, x: usize, y: usize) -> (Dungeon, bool) {
+    let terrain = d.terrain_map[x][y];
+    let opened = match terrain.opens_to() {
+        Some(open) => {
+            if terrain.needs_key() && !d.player.remove_item(ItemKind::Key) {
+                return (d, false);
+            }
+            d = set_terrain(d, x, y, open);
+            true
+        }
+        None => false,
+    };
+    (d, opened)
+}
+
+/// Change the terrain of a cell, keeping the hardness map in step with it.
+/// Rock keeps whatever hardness the generator gave it.
+fn set_terrain(mut d: Dungeon, x: usize, y: usize, terrain: Terrain) -> Dungeon {
+    d.terrain_map[x][y] = terrain;
+    if terrain.passable() || terrain.opens_to().is_some() {
+        d.hardness_map[x][y] = 0;
+    } else if !terrain.diggable() {
+        d.hardness_map[x][y] = 255;
+    }
+    d
+}
+
+/// Give the solid rock a graded hardness from smoothed value noise, so
+/// there are soft seams and hard cores to dig through.
+fn add_rock_hardness(mut d: Dungeon, rng: &mut StdRng) -> Dungeon {
+    const CELL: usize = 8;
+    let lattice_x = d.dungeon_x / CELL + 2;
+    let lattice_y = d.dungeon_y / CELL + 2;
+    let mut lattice = vec![vec![0.0f32; lattice_y]; lattice_x];
+    for row in lattice.iter_mut() {
+        for value in row.iter_mut() {
+            *value = rng.gen_range(0.0..1.0);
+        }
+    }
+
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    for x in 0..d.dungeon_x {
+        for y in 0..d.dungeon_y {
+            let (lx, ly) = (x / CELL, y / CELL);
+            let tx = smooth((x % CELL) as f32 / CELL as f32);
+            let ty = smooth((y % CELL) as f32 / CELL as f32);
+            let top = lattice[lx][ly] + (lattice[lx][ly + 1] - lattice[lx][ly]) * ty;
+            let bottom = lattice[lx + 1][ly] + (lattice[lx + 1][ly + 1] - lattice[lx + 1][ly]) * ty;
+            let noise = top + (bottom - top) * tx;
+            let jitter = rng.gen_range(-10..=10);
+
+            let hardness = 30 + (noise * 220.0) as i32 + jitter;
+            d.hardness_map[x][y] = hardness.clamp(1, 254) as u8;
         }
     }
     d
 }
 
+/// Chip away at a diggable cell, turning it into corridor once its hardness
+/// is gone. Returns whether any digging happened.
+pub fn dig(mut d: Dungeon, x: usize, y: usize, power: u8) -> (Dungeon, bool) {
+    if power == 0 || !d.terrain_map[x][y].diggable() {
+        return (d, false);
+    }
+    d.hardness_map[x][y] = d.hardness_map[x][y].saturating_sub(power);
+    if d.hardness_map[x][y] == 0 {
+        d = set_terrain(d, x, y, Terrain::Corridor);
+    }
+    (d, true)
+}
+
+/// Dig a corridor through a cell unless it is already part of a room.
+fn carve_corridor(mut d: Dungeon, x: usize, y: usize) -> Dungeon {
+    if d.terrain_map[x][y] != Terrain::Floor {
+        d = set_terrain(d, x, y, Terrain::Corridor);
+    }
+    d
+}
+
 fn add_hallways(mut d: Dungeon, mut rooms: Vec<(usize, usize, usize, usize)>) -> Dungeon {
     rooms.sort_by_key(|k| k.1);
     for i in 0..(rooms.len() - 1) {
@@ -221,54 +933,33 @@ fn add_hallways(mut d: Dungeon, mut rooms: Vec<(usize, usize, usize, usize)>) ->
 
         if mid_x < 1000 {
             for j in (rooms[i].1 + rooms[i].2)..rooms[i + 1].1 {
-                d.char_map[mid_x][j] = '#';
-                d.hardness_map[mid_x][j] = 0;
+                d = carve_corridor(d, mid_x, j);
             }
         } else if mid_y < 1000 {
             if (rooms[i].0 + rooms[i].3) > rooms[i + 1].0 {
                 for j in rooms[i + 1].0..(rooms[i].0 + rooms[i].3) {
-                    if d.char_map[j][mid_y] != '.' {
-                        d.char_map[j][mid_y] = '#';
-                        d.hardness_map[j][mid_y] = 0;
-                    }
+                    d = carve_corridor(d, j, mid_y);
                 }
             } else {
                 for j in rooms[i].0..rooms[i + 1].0 {
-                    if d.char_map[j][mid_y] != '.' {
-                        d.char_map[j][mid_y] = '#';
-                        d.hardness_map[j][mid_y] = 0;
-                    }
+                    d = carve_corridor(d, j, mid_y);
                 }
             }
-        } else {
-            if rooms[i].0 > rooms[i + 1].0 {
-                for j in rooms[i + 1].0..(rooms[i].0 + rooms[i].3) {
-                    if d.char_map[j][rooms[i].1] != '.' {
-                        d.char_map[j][rooms[i].1] = '#';
-                        d.hardness_map[j][rooms[i].1] = 0;
-                    }
-                }
+        } else if rooms[i].0 > rooms[i + 1].0 {
+            for j in rooms[i + 1].0..(rooms[i].0 + rooms[i].3) {
+                d = carve_corridor(d, j, rooms[i].1);
+            }
 
-                for j in rooms[i].1..rooms[i + 1].1 {
-                    if d.char_map[rooms[i + 1].0][j] != '.' {
-                        d.char_map[rooms[i + 1].0][j] = '#';
-                        d.hardness_map[rooms[i + 1].0][j] = 0;
-                    }
-                }
-            } else {
-                for j in (rooms[i].0)..(rooms[i + 1].0) {
-                    if d.char_map[j][rooms[i + 1].1] != '.' {
-                        d.char_map[j][rooms[i + 1].1] = '#';
-                        d.hardness_map[j][rooms[i + 1].1] = 0;
-                    }
-                }
+            for j in rooms[i].1..rooms[i + 1].1 {
+                d = carve_corridor(d, rooms[i + 1].0, j);
+            }
+        } else {
+            for j in (rooms[i].0)..(rooms[i + 1].0) {
+                d = carve_corridor(d, j, rooms[i + 1].1);
+            }
 
-                for j in rooms[i].1..rooms[i + 1].1 {
-                    if d.char_map[rooms[i].0][j] != '.' {
-                        d.char_map[rooms[i].0][j] = '#';
-                        d.hardness_map[rooms[i].0][j] = 0;
-                    }
-                }
+            for j in rooms[i].1..rooms[i + 1].1 {
+                d = carve_corridor(d, rooms[i].0, j);
             }
         }
     }
@@ -286,43 +977,98 @@ fn determine_overlap(mut overlap: Vec<(usize, usize)>) -> usize {
     if max == 1000 {
         return max;
     }
-    let mid = (max + min) / 2;
-    mid
+    (max + min) / 2
 }
 
+/// Dijkstra maps of the cost for a monster to reach the player from every
+/// cell, one for walkers and one for monsters that can tunnel through rock.
 pub fn calculate_distance_map(mut d: Dungeon) -> Dungeon {
-    let px = d.player.position_x as i16;
-    let py = d.player.position_y as i16;
+    let player = (d.player.position_x, d.player.position_y);
+    d.distance_map = goal_map(&d, player, false);
+    d.tunnel_map = goal_map(&d, player, true);
+    d.safety_map = flee::safety_map(&d, true);
+    d
+}
 
-    for x in 0..d.dungeon_x {
-        for y in 0..d.dungeon_y {
-            if d.hardness_map[x][y] > 2 {
-                d.distance_map[x][y] = 1055;
+/// Dijkstra map of the cost to walk (or tunnel) to a goal cell.
+pub fn goal_map(d: &Dungeon, goal: (usize, usize), tunnels: bool) -> [[u16; 80]; 30] {
+    if tunnels {
+        dijkstra_map(d, goal, |terrain, hardness| {
+            if terrain.diggable() {
+                1 + (hardness as u16).div_ceil(TUNNEL_POWER as u16)
             } else {
-                let xz = x as i16;
-                let yz = y as i16;
-                let manhatan_distance = (px - xz).abs() + (py - yz).abs();
-                d.distance_map[x][y] = manhatan_distance as u16;
+                terrain.movement_cost()
             }
+        })
+    } else {
+        dijkstra_map(d, goal, |terrain, _| terrain.movement_cost())
+    }
+}
+
+/// The 3x3 block of cells centred on (and including) a cell.
+fn neighbourhood(x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
+    ((x - 1)..(x + 2)).flat_map(move |nx| ((y - 1)..(y + 2)).map(move |ny| (nx, ny)))
+}
+
+/// Bresenham walk between two cells, giving every cell after `from` up to
+/// and including `to`.
+fn line(from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+    let (mut x, mut y) = (from.0 as i32, from.1 as i32);
+    let (tx, ty) = (to.0 as i32, to.1 as i32);
+    let dx = (tx - x).abs();
+    let dy = -(ty - y).abs();
+    let sx = if x < tx { 1 } else { -1 };
+    let sy = if y < ty { 1 } else { -1 };
+    let mut err = dx + dy;
+
+    let mut cells = Vec::new();
+    while (x, y) != (tx, ty) {
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
         }
+        cells.push((x as usize, y as usize));
     }
-    d
+    cells
 }
 
-fn place_monsters(mut d: Dungeon) -> Dungeon {
-    let mut mon = 0;
+fn dijkstra_map(
+    d: &Dungeon,
+    goal: (usize, usize),
+    step_cost: impl Fn(Terrain, u8) -> u16,
+) -> [[u16; 80]; 30] {
+    let mut map = [[IMPASSABLE; 80]; 30];
+    let mut heap = BinaryHeap::new();
+    let known_traps = trap::known_traps(d);
 
-    while mon < d.monsters.len() {
-        let x = rand::thread_rng().gen_range(1..d.dungeon_x);
-        let y = rand::thread_rng().gen_range(1..d.dungeon_y);
+    map[goal.0][goal.1] = 0;
+    heap.push(Reverse((0, goal.0, goal.1)));
 
-        if d.hardness_map[x][y] < 2 {
-            d.monsters[mon].pos_x = x;
-            d.monsters[mon].pos_y = y;
-            mon += 1;
+    while let Some(Reverse((cost, x, y))) = heap.pop() {
+        if cost > map[x][y] {
+            continue;
+        }
+        for (nx, ny) in neighbourhood(x, y) {
+            let mut step = step_cost(d.terrain_map[nx][ny], d.hardness_map[nx][ny]);
+            if step == IMPASSABLE {
+                continue;
+            }
+            if known_traps[nx][ny] {
+                step += trap::AVOID_COST;
+            }
+            let next = cost + step;
+            if next < map[nx][ny] {
+                map[nx][ny] = next;
+                heap.push(Reverse((next, nx, ny)));
+            }
         }
     }
-    d
+    map
 }
 
 pub fn monster_map(mut d: Dungeon) -> Dungeon {
@@ -341,27 +1087,115 @@ pub fn monster_map(mut d: Dungeon) -> Dungeon {
     d
 }
 
-pub fn process_attack(mut d : Dungeon) -> (Monster, usize, bool) {
-    let x = d.player.target.0;
-    let y = d.player.target.1; 
-    let mut mon_id: usize = 100;
-    let mut attack_proccessed = false;
-    for i in 0..d.monsters.len() {
-        if d.monsters[i].pos_x == x && d.monsters[i].pos_y == y {
-            mon_id = i;
+pub fn process_attack(mut d: Dungeon) -> Dungeon {
+    let (x, y) = d.player.target;
+    let target = d
+        .monsters
+        .iter()
+        .position(|m| m.alive && m.pos_x == x && m.pos_y == y);
+    if let Some(i) = target {
+        let hp = d.monsters[i].process_combat(d.player.attack_power());
+        d = hurt_monster(d, i, d.monsters[i].hp - hp);
+    }
+    d
+}
+
+/// The player deals damage to a monster, earning its xp if it dies.
+pub fn hurt_monster(mut d: Dungeon, monster: usize, damage: i32) -> Dungeon {
+    d = shop::provoke(d, monster);
+    d.monsters[monster].hp -= damage;
+    if d.monsters[monster].hp <= 0 {
+        d.stats.kills += 1;
+        d = kill_monster(d, monster);
+        d = experience::gain_xp(d, d.monsters[monster].kind.info().xp);
+    }
+    d
+}
+
+/// Take a monster out of play, leaving its corpse where it fell. Bosses
+/// also leave their drop, making room for it if the floor is full of corpses.
+pub fn kill_monster(mut d: Dungeon, monster: usize) -> Dungeon {
+    let (x, y) = (d.monsters[monster].pos_x, d.monsters[monster].pos_y);
+    d.monsters[monster].alive = false;
+    d.mon_map[x][y] = false;
+    if let Some(item) = d.monsters[monster].carrying.take() {
+        d = drop_item(d, item, x, y).0;
+    }
+    if let Some(boss) = boss::boss_info(d.monsters[monster].kind) {
+        let (with_drop, dropped) = drop_item(d, boss.drop, x, y);
+        d = with_drop;
+        if !dropped {
+            if let Some(corpse) = d
+                .items
+                .iter()
+                .position(|i| i.is_some_and(|i| i.kind == ItemKind::Corpse))
+            {
+                d.items[corpse] = None;
+                d = drop_item(d, boss.drop, x, y).0;
+            }
         }
     }
-    if mon_id != 100 {
-        let hp_after = d.monsters[mon_id].process_combat(d.player.attack); 
-        d.monsters[mon_id].hp = hp_after;
-        if d.monsters[mon_id].hp <= 0 {
-            d.monsters[mon_id].alive = false;
+    d = drop_item(d, ItemKind::Corpse, x, y).0;
+    d = loot::drop_loot(d, monster);
+    d = push_event(
+        d,
+        Event::MonsterDied {
+            kind: d.monsters[monster].kind,
+            pos_x: x,
+            pos_y: y,
+        },
+    );
+    quest::check_boss(d, monster)
+}
+
+/// A monster hits the player with the given attack.
+pub fn hurt_player(mut d: Dungeon, kind: MonsterKind, attack: i32) -> Dungeon {
+    if !d.player.alive {
+        return d;
+    }
+    let hp = d.player.process_combat(attack);
+    let damage = d.player.hp - hp;
+    d.player.hp = hp;
+    d = push_event(d, Event::PlayerHit { kind, damage });
+    if d.player.hp <= 0 {
+        d.player.alive = false;
+        d = push_event(d, Event::PlayerDied { kind });
+    }
+    d
+}
+
+/// Every awake monster next to the player that isn't running away attacks,
+/// except the ones listed, which already did something else this turn.
+fn process_monster_attacks(mut d: Dungeon, busy: &[usize]) -> Dungeon {
+    for i in 0..d.monsters.len() {
+        let monster = d.monsters[i];
+        let adjacent = monster.pos_x.abs_diff(d.player.position_x) <= 1
+            && monster.pos_y.abs_diff(d.player.position_y) <= 1;
+        if monster.alive && monster.awake && !monster.fleeing && adjacent && !busy.contains(&i) {
+            d = hurt_player(d, monster.kind, monster.attack);
+            d = ability::poison_player(d, i);
         }
-        attack_proccessed = true;
-    } else {
-        return (d.monsters[0], mon_id, attack_proccessed); 
     }
-    (d.monsters[mon_id], mon_id, attack_proccessed)
+    d
+}
+
+/// Queue an event for the front-end; the oldest is dropped when full.
+pub fn push_event(mut d: Dungeon, event: Event) -> Dungeon {
+    if d.events.iter().all(|e| e.is_some()) {
+        d.events.rotate_left(1);
+        d.events[d.events.len() - 1] = None;
+    }
+    if let Some(slot) = d.events.iter_mut().find(|e| e.is_none()) {
+        *slot = Some(event);
+    }
+    d
+}
+
+/// Hand over the queued events, oldest first, and clear the queue.
+pub fn take_events(mut d: Dungeon) -> (Dungeon, Vec<Event>) {
+    let events = d.events.iter().flatten().copied().collect();
+    d.events = [None; 16];
+    (d, events)
 }
 
 pub fn process_target_monster(mut p: Player, x : usize, y : usize) -> Player {
@@ -369,16 +1203,139 @@ pub fn process_target_monster(mut p: Player, x : usize, y : usize) -> Player {
     p
 }
 
+/// Everything that happens after the player acts: the player may spot a
+/// trap nearby, effects tick, the player gets hungrier and recovers a little
+/// hp and mana, then the monsters take their turn, unless a hasted player
+/// gets this one free.
+pub fn take_turn(mut d: Dungeon) -> Dungeon {
+    d.turn += 1;
+    d = shop::check_theft(d);
+    d = trap::notice_traps(d);
+    d = status::tick_statuses(d);
+    d = hunger::tick_hunger(d);
+    d = rest::regenerate(d);
+    d = spell::regenerate_mana(d);
+    if d.player.statuses.has(StatusKind::Haste) && d.turn.is_multiple_of(2) {
+        d.noise = 0;
+        return d;
+    }
+    process_monster_moves_attack(d)
+}
+
 pub fn process_monster_moves_attack(mut d: Dungeon) -> Dungeon {
-    
-    for i in 0..d.monsters.len() {
-        let moves = d.determine_monster_move(i);
-        
-        d.monsters[i].pos_x = moves.0;
-        d.monsters[i].pos_y = moves.1;
+    d = perception::update_perception(d);
+    d = pack::alert_packs(d);
+    d = flee::update_morale(d);
+    d = boss::update_phases(d);
+    let (after_abilities, mut busy) = ability::use_abilities(d);
+    d = after_abilities;
+    busy.extend(
+        (0..d.monsters.len())
+            .filter(|&i| d.monsters[i].peaceful || d.monsters[i].statuses.loses_turn(d.turn)),
+    );
+    let before: Vec<(usize, usize)> = d.monsters.iter().map(|m| (m.pos_x, m.pos_y)).collect();
+    d = movement::resolve_monster_moves(d, &busy);
+    d = trap::spring_monster_traps(d, &before);
+    let (after_slams, slammed) = boss::boss_slams(d);
+    busy.extend(slammed);
+    d = process_monster_attacks(after_slams, &busy);
+    d.noise = 0;
+    monster_map(d)
+}
 
-        d = monster_map(d);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ascii_map::load_map;
+
+    const ABOVE: &str = "\
+%%%%%%%%%%
+%@.g..).>%
+%%%%%%%%%%
+";
+
+    const BELOW: &str = "\
+%%%%%%%%%%
+%<..r...@%
+%%%%%%%%%%
+";
+
+    /// A `Levels` that already holds `BELOW` as the second level.
+    fn levels_with_below() -> Levels {
+        let mut below = load_map(BELOW).unwrap();
+        below.depth = 2;
+        let mut levels = Levels::default();
+        levels.left.insert(2, below);
+        levels
     }
 
-    d
-}
\ No newline at end of file
+    #[test]
+    fn levels_stay_as_they_were_left() {
+        let mut levels = levels_with_below();
+        let mut d = load_map(ABOVE).unwrap();
+        d.monsters[0].alive = false;
+        d.items = [None; 40];
+
+        d.leaving_for = Some(2);
+        d = levels.travel(d);
+        assert_eq!(d.depth, 2);
+        assert_eq!((d.player.position_x, d.player.position_y), (1, 1));
+        assert_eq!(d.stats.deepest_reached, 2);
+        d.monsters[0].alive = false;
+
+        d.leaving_for = Some(1);
+        d = levels.travel(d);
+        assert_eq!(d.depth, 1);
+        assert_eq!((d.player.position_x, d.player.position_y), (1, 8));
+        assert!(!d.monsters[0].alive);
+        assert!(d.items.iter().all(Option::is_none));
+
+        d.leaving_for = Some(2);
+        d = levels.travel(d);
+        assert!(!d.monsters[0].alive);
+    }
+
+    #[test]
+    fn arrives_beside_a_monster_on_the_stairs() {
+        let mut levels = levels_with_below();
+        levels.left.get_mut(&2).unwrap().monsters[0].pos_y = 1;
+        let mut d = load_map(ABOVE).unwrap();
+        d.leaving_for = Some(2);
+        d = levels.travel(d);
+        assert_eq!((d.player.position_x, d.player.position_y), (1, 2));
+    }
+
+    #[test]
+    fn amulet_stays_on_the_deepest_level_until_taken() {
+        let amulets = |d: &Dungeon| {
+            d.items
+                .iter()
+                .flatten()
+                .filter(|i| i.kind == ItemKind::AncientAmulet)
+                .count()
+        };
+        let mut levels = Levels::default();
+        let mut above = load_map(ABOVE).unwrap();
+        above.depth = 3;
+        levels.left.insert(3, above);
+        let mut d = load_map(BELOW).unwrap();
+        d.depth = 4;
+        d = quest::place_amulet(d);
+        assert_eq!(amulets(&d), 1);
+
+        d.leaving_for = Some(3);
+        d = levels.travel(d);
+        d.leaving_for = Some(4);
+        d = levels.travel(d);
+        assert_eq!(amulets(&d), 1);
+
+        d.items = [None; 40];
+        assert!(d.player.add_item(ItemKind::AncientAmulet));
+        d.leaving_for = Some(3);
+        d = levels.travel(d);
+        d.leaving_for = Some(4);
+        d = levels.travel(d);
+        assert_eq!(amulets(&d), 0);
+        assert!(d.player.has_item(ItemKind::AncientAmulet));
+    }
+}
@@ -0,0 +1,342 @@
+use crate::dungeon::event::Event;
+use crate::dungeon::monster::{Monster, MonsterKind};
+use crate::dungeon::status::{Status, StatusKind};
+use crate::dungeon::{hurt_player, neighbourhood, push_event, Dungeon};
+
+/// Most abilities a monster kind can have.
+pub const MAX_ABILITIES: usize = 2;
+
+/// How long spit in the eyes leaves the player confused.
+const SPIT_CONFUSION_TURNS: u32 = 3;
+
+/// Something a monster can do besides walking up and hitting the player.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Ability {
+    /// Spit at the player from up to `range` cells away, given a clear line
+    /// of sight, leaving them confused. Takes the monster's turn.
+    Spit { range: usize },
+    /// Melee hits also poison the player for this many turns.
+    Poison { turns: u32 },
+    /// Heal this many hp, without using up the turn.
+    Regenerate { amount: i32 },
+    /// Call a monster into a free cell next to this one. Takes the turn.
+    Summon { kind: MonsterKind },
+    /// Take the last item in the player's pack from next to them, then run
+    /// off with it. Takes the turn.
+    Steal,
+}
+
+/// An ability and how many turns the monster must wait between uses.
+pub struct AbilityInfo {
+    pub ability: Ability,
+    pub cooldown: u32,
+}
+
+fn knows_player(d: &Dungeon, monster: &Monster) -> bool {
+    monster.awake && monster.last_seen == Some((d.player.position_x, d.player.position_y))
+}
+
+fn reach(d: &Dungeon, monster: &Monster) -> usize {
+    let dx = monster.pos_x.abs_diff(d.player.position_x);
+    let dy = monster.pos_y.abs_diff(d.player.position_y);
+    dx.max(dy)
+}
+
+/// Try one ability, returning whether it was used.
+fn try_ability(mut d: Dungeon, i: usize, ability: Ability) -> (Dungeon, bool) {
+    let monster = d.monsters[i];
+    let player = (d.player.position_x, d.player.position_y);
+    match ability {
+        Ability::Spit { range } => {
+            let distance = reach(&d, &monster);
+            let clear = d.line_of_sight((monster.pos_x, monster.pos_y), player);
+            if !knows_player(&d, &monster) || distance < 2 || distance > range || !clear {
+                return (d, false);
+            }
+            d = push_event(d, Event::MonsterSpat { kind: monster.kind });
+            d.player.statuses.add(Status {
+                kind: StatusKind::Confusion,
+                turns: SPIT_CONFUSION_TURNS,
+                strength: 1,
+                source: Some(monster.kind),
+            });
+            (hurt_player(d, monster.kind, monster.attack), true)
+        }
+        Ability::Regenerate { amount } => {
+            if monster.hp >= monster.max_hp {
+                return (d, false);
+            }
+            d.monsters[i].hp = (monster.hp + amount).min(monster.max_hp);
+            (d, true)
+        }
+        Ability::Summon { kind } => {
+            if !knows_player(&d, &monster) {
+                return (d, false);
+            }
+            let cell = neighbourhood(monster.pos_x, monster.pos_y).find(|&(x, y)| {
+                d.terrain_map[x][y].passable()
+                    && (x, y) != player
+                    && !d
+                        .monsters
+                        .iter()
+                        .any(|m| m.alive && (m.pos_x, m.pos_y) == (x, y))
+            });
+            // A dead leader's slot still ties its pack together
+            let slot = (0..d.monsters.len()).find(|&s| {
+                !d.monsters[s].alive && !d.monsters.iter().any(|m| m.alive && m.pack == Some(s))
+            });
+            let (Some((x, y)), Some(slot)) = (cell, slot) else {
+                return (d, false);
+            };
+            d.monsters[slot] = Monster::new(kind);
+            d.monsters[slot].pos_x = x;
+            d.monsters[slot].pos_y = y;
+            d.monsters[slot].last_seen = Some(player);
+            d.monsters[slot].pack = monster.pack;
+            d = push_event(
+                d,
+                Event::MonsterSummoned {
+                    kind: monster.kind,
+                    summoned: kind,
+                },
+            );
+            (d, true)
+        }
+        Ability::Steal => {
+            let item = d.player.inventory.iter().rev().flatten().next().copied();
+            let Some(item) = item else {
+                return (d, false);
+            };
+            if !knows_player(&d, &monster) || reach(&d, &monster) > 1 || monster.carrying.is_some()
+            {
+                return (d, false);
+            }
+            d.player.remove_item(item);
+            d.monsters[i].carrying = Some(item);
+            d = push_event(
+                d,
+                Event::ItemStolen {
+                    kind: monster.kind,
+                    item,
+                },
+            );
+            (d, true)
+        }
+        // Used when the monster hits, see `poison_player`
+        Ability::Poison { .. } => (d, false),
+    }
+}
+
+/// Count down every cooldown and let monsters use whatever abilities are
+/// ready. Returns the monsters whose ability took their turn, so they
+/// neither move nor attack. Monsters summoned this turn arrive at the end of
+/// it and are returned too.
+pub fn use_abilities(mut d: Dungeon) -> (Dungeon, Vec<usize>) {
+    let mut busy = Vec::new();
+    let absent: Vec<usize> = (0..d.monsters.len())
+        .filter(|&i| !d.monsters[i].alive)
+        .collect();
+    for i in 0..d.monsters.len() {
+        let idle = d.monsters[i].statuses.loses_turn(d.turn);
+        if !d.monsters[i].alive || !d.monsters[i].awake || idle || absent.contains(&i) {
+            continue;
+        }
+        let abilities = d.monsters[i].kind.info().abilities;
+        for (k, info) in abilities.iter().enumerate() {
+            if d.monsters[i].ability_cooldowns[k] > 0 {
+                d.monsters[i].ability_cooldowns[k] -= 1;
+                continue;
+            }
+            if busy.contains(&i) {
+                continue;
+            }
+            let (used_on, used) = try_ability(d, i, info.ability);
+            d = used_on;
+            if used {
+                d.monsters[i].ability_cooldowns[k] = info.cooldown;
+                if !matches!(info.ability, Ability::Regenerate { .. }) {
+                    busy.push(i);
+                }
+            }
+        }
+    }
+    busy.extend(absent.into_iter().filter(|&i| d.monsters[i].alive));
+    (d, busy)
+}
+
+/// Poison the player after a hit by monster `i`, if its kind is venomous and
+/// its poison is ready.
+pub fn poison_player(mut d: Dungeon, i: usize) -> Dungeon {
+    let abilities = d.monsters[i].kind.info().abilities;
+    for (k, info) in abilities.iter().enumerate() {
+        if let Ability::Poison { turns } = info.ability {
+            if d.monsters[i].ability_cooldowns[k] == 0 && d.player.alive {
+                d.monsters[i].ability_cooldowns[k] = info.cooldown;
+                d.player.statuses.add(Status {
+                    kind: StatusKind::Poison,
+                    turns,
+                    strength: 1,
+                    source: Some(d.monsters[i].kind),
+                });
+                d = push_event(
+                    d,
+                    Event::PlayerPoisoned {
+                        kind: d.monsters[i].kind,
+                    },
+                );
+            }
+        }
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ascii_map::load_map;
+    use crate::dungeon::item::ItemKind;
+    use crate::dungeon::{monster_map, take_turn};
+
+    /// Load a map, let every monster know where the player is, make any
+    /// changes the test needs and play a few turns.
+    fn run(map: &str, turns: usize, setup: impl FnOnce(&mut Dungeon)) -> Dungeon {
+        let mut d = load_map(map).expect("ability test map loads");
+        let player = (d.player.position_x, d.player.position_y);
+        for monster in d.monsters.iter_mut().filter(|m| m.alive) {
+            monster.last_seen = Some(player);
+        }
+        setup(&mut d);
+        for _ in 0..turns {
+            d = monster_map(d);
+            d = take_turn(d);
+        }
+        d
+    }
+
+    fn living(d: &Dungeon, kind: MonsterKind) -> usize {
+        d.monsters
+            .iter()
+            .filter(|m| m.alive && m.kind == kind)
+            .count()
+    }
+
+    const SPIT_MAP: &str = "\
+%%%%%%%%
+%@....s%
+%%%%%%%%
+";
+
+    #[test]
+    fn spitter_spits_across_the_room() {
+        let d = run(SPIT_MAP, 1, |_| {});
+        assert!(d.player.hp < 100);
+        assert!(d.player.statuses.has(StatusKind::Confusion));
+    }
+
+    #[test]
+    fn spitter_waits_for_its_cooldown() {
+        let d = run(SPIT_MAP, 2, |_| {});
+        assert_eq!(d.player.hp, 98);
+    }
+
+    #[test]
+    fn walls_stop_spit() {
+        let map = "\
+%%%%%%%
+%@.%.s%
+%%%%%%%
+";
+        let d = run(map, 3, |_| {});
+        assert_eq!(d.player.hp, 100);
+    }
+
+    #[test]
+    fn rat_bites_poison() {
+        let map = "\
+%%%%
+%@r%
+%%%%
+";
+        let d = run(map, 3, |_| {});
+        assert!(d.player.statuses.has(StatusKind::Poison));
+        assert_eq!(d.player.hp, 98);
+    }
+
+    #[test]
+    fn rock_worm_regenerates() {
+        let map = "\
+%%%%%%%%%%
+%@......w%
+%%%%%%%%%%
+";
+        let d = run(map, 3, |d| d.monsters[0].hp = 5);
+        assert_eq!(d.monsters[0].hp, 7);
+    }
+
+    #[test]
+    fn chief_summons_a_goblin_next_to_it() {
+        let map = "\
+%%%%%%%%%%
+%@......G%
+%%%%%%%%%%
+";
+        let d = run(map, 1, |_| {});
+        let chief = d.monsters[0];
+        let next_to_chief = d.monsters.iter().any(|m| {
+            m.alive
+                && m.kind == MonsterKind::Goblin
+                && m.pos_x
+                    .abs_diff(chief.pos_x)
+                    .max(m.pos_y.abs_diff(chief.pos_y))
+                    == 1
+        });
+        assert!(next_to_chief);
+    }
+
+    #[test]
+    fn summons_leave_a_dead_leaders_slot_alone() {
+        let map = "\
+%%%%%%%%%%
+%@......G%
+%%%%%%%%%%
+%g%%%%%%%%
+%%%%%%%%%%
+";
+        let d = run(map, 1, |d| d.monsters[1].pack = Some(2));
+        assert!(!d.monsters[2].alive);
+        assert_eq!(d.monsters[1].pack, Some(2));
+        assert_eq!(living(&d, MonsterKind::Goblin), 2);
+    }
+
+    #[test]
+    fn no_room_no_summons() {
+        let map = "\
+%%%%%
+%@..%
+%%%%%
+%%G%%
+%%%%%
+";
+        let d = run(map, 1, |_| {});
+        assert_eq!(living(&d, MonsterKind::Goblin), 0);
+    }
+
+    const THIEF_MAP: &str = "\
+%%%%%%%%%%%%
+%@g........%
+%%%%%%%%%%%%
+";
+
+    #[test]
+    fn goblin_steals_the_last_item_in_the_pack() {
+        let d = run(THIEF_MAP, 1, |_| {});
+        assert_eq!(d.monsters[0].carrying, Some(ItemKind::Darts));
+        assert!(!d.player.has_item(ItemKind::Darts));
+    }
+
+    #[test]
+    fn thief_runs_off_with_the_loot() {
+        let d = run(THIEF_MAP, 4, |_| {});
+        assert!(d.monsters[0].pos_y > 3);
+    }
+}
@@ -0,0 +1,195 @@
+//! Hand-written levels in plain text.
+//!
+//! A map uses the glyphs `Dungeon::print` shows for terrain, plus `@` for the
+//! player, a monster's character for a monster and an item's glyph for an
+//! item lying on the floor. Actors and items always stand on floor. Lines may
+//! be ragged, missing cells are rock. Every cell on the edge of the map must
+//! be wall or rock.
+//!
+//! A map is a picture of what the player would see, not a save file. Only
+//! the top thing in each cell is written, so items under actors and all but
+//! one item of a stack are lost, and so are traps, sleep and the hardness of
+//! the rock. Item kinds that share a glyph load as the first kind with it
+//! (see `ItemInfo::glyph`). Terrain, actors and items with a glyph of their
+//! own come back as they were.
+
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::monster::{Monster, MonsterKind};
+use crate::dungeon::terrain::Terrain;
+use crate::dungeon::{
+    calculate_distance_map, drop_item, empty_dungeon, monster_map, set_terrain, Dungeon,
+};
+
+/// Hand-written maps don't say how hard their rock is, so it all gets this.
+const LOADED_ROCK_HARDNESS: u8 = 128;
+
+pub fn load_map(text: &str) -> Result<Dungeon, String> {
+    let mut d = empty_dungeon();
+    let lines: Vec<&str> = text.lines().collect();
+    let height = lines.len();
+    let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+
+    if height < 3 || width < 3 {
+        return Err(String::from("map must be at least 3x3"));
+    }
+    if height > d.terrain_map.len() || width > d.terrain_map[0].len() {
+        return Err(format!(
+            "map is {}x{}, at most {}x{} is supported",
+            height,
+            width,
+            d.terrain_map.len(),
+            d.terrain_map[0].len()
+        ));
+    }
+    d.dungeon_x = height;
+    d.dungeon_y = width;
+
+    let mut player_found = false;
+    let mut monsters = 0;
+    for (x, line) in lines.iter().enumerate() {
+        for y in 0..width {
+            d.hardness_map[x][y] = LOADED_ROCK_HARDNESS;
+            let glyph = line.chars().nth(y).unwrap_or(' ');
+
+            if glyph == d.player.character {
+                if player_found {
+                    return Err(format!("second player at line {}, column {}", x + 1, y + 1));
+                }
+                player_found = true;
+                d.player.position_x = x;
+                d.player.position_y = y;
+                d = set_terrain(d, x, y, Terrain::Floor);
+            } else if let Some(kind) = MonsterKind::from_glyph(glyph) {
+                if monsters == d.monsters.len() {
+                    return Err(format!("more than {} monsters", d.monsters.len()));
+                }
+                d.monsters[monsters] = Monster::new(kind);
+                d.monsters[monsters].pos_x = x;
+                d.monsters[monsters].pos_y = y;
+                monsters += 1;
+                d = set_terrain(d, x, y, Terrain::Floor);
+            } else if let Some(kind) = ItemKind::from_glyph(glyph) {
+                let (with_item, dropped) = drop_item(d, kind, x, y);
+                if !dropped {
+                    return Err(format!("more than {} items", d.items.len()));
+                }
+                d = set_terrain(with_item, x, y, Terrain::Floor);
+            } else if let Some(terrain) = Terrain::from_glyph(glyph) {
+                d = set_terrain(d, x, y, terrain);
+            } else {
+                return Err(format!(
+                    "unknown glyph '{}' at line {}, column {}",
+                    glyph,
+                    x + 1,
+                    y + 1
+                ));
+            }
+
+            let edge = x == 0 || x == height - 1 || y == 0 || y == width - 1;
+            let terrain = d.terrain_map[x][y];
+            if edge && terrain != Terrain::Wall && terrain != Terrain::Rock {
+                return Err(format!(
+                    "map edge at line {}, column {} must be wall or rock",
+                    x + 1,
+                    y + 1
+                ));
+            }
+        }
+    }
+    if !player_found {
+        return Err(String::from("map has no player"));
+    }
+
+    // Take the unused roster slots out of play
+    for monster in d.monsters.iter_mut().skip(monsters) {
+        monster.park();
+    }
+
+    d = monster_map(d);
+    d = calculate_distance_map(d);
+    Ok(d)
+}
+
+pub fn export_map(d: &Dungeon) -> String {
+    let mut text = String::new();
+    for x in 0..d.dungeon_x {
+        for y in 0..d.dungeon_y {
+            let monster = d
+                .monsters
+                .iter()
+                .find(|m| m.alive && m.pos_x == x && m.pos_y == y);
+            let item = d
+                .items
+                .iter()
+                .flatten()
+                .find(|i| i.pos_x == x && i.pos_y == y);
+
+            let glyph = if d.player.position_x == x && d.player.position_y == y {
+                d.player.character
+            } else if let Some(monster) = monster {
+                monster.character
+            } else if let Some(item) = item {
+                item.kind.glyph()
+            } else {
+                d.terrain_map[x][y].glyph()
+            };
+            text.push(glyph);
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "\
+%%%%%%%%%%
+%..g.....%
+%.@.~~.,.%
+%%%%+%%'%%
+   %.*.r.%
+   %%%%%%%
+";
+
+    #[test]
+    fn round_trip_keeps_the_map() {
+        let d = load_map(MAP).expect("map loads");
+        assert_eq!(export_map(&d), MAP);
+
+        let again = load_map(&export_map(&d)).expect("exported map loads");
+        assert_eq!(again.terrain_map, d.terrain_map);
+        let place = |d: &Dungeon| (d.player.position_x, d.player.position_y);
+        assert_eq!(place(&again), (2, 2));
+        let monsters: Vec<_> = again
+            .monsters
+            .iter()
+            .filter(|m| m.alive)
+            .map(|m| (m.kind, m.pos_x, m.pos_y))
+            .collect();
+        assert_eq!(
+            monsters,
+            [(MonsterKind::Goblin, 1, 3), (MonsterKind::Rat, 4, 7)]
+        );
+        let items: Vec<_> = again
+            .items
+            .iter()
+            .flatten()
+            .map(|i| (i.kind, i.pos_x, i.pos_y))
+            .collect();
+        assert_eq!(items, [(ItemKind::Corpse, 2, 7), (ItemKind::Crown, 4, 5)]);
+    }
+
+    #[test]
+    fn rejects_an_open_edge() {
+        assert!(load_map("%%%\n%@.\n%%%\n").is_err());
+    }
+
+    #[test]
+    fn shipped_tutorial_round_trips() {
+        let text = include_str!("../../../static/maps/tutorial.txt");
+        let d = load_map(text).expect("tutorial loads");
+        assert_eq!(export_map(&d), text);
+    }
+}
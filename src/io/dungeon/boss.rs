@@ -0,0 +1,276 @@
+use crate::dungeon::event::Event;
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::monster::{Monster, MonsterKind, UNPLACED};
+use crate::dungeon::spawn;
+use crate::dungeon::status::{Status, StatusKind};
+use crate::dungeon::terrain::{Terrain, IMPASSABLE};
+use crate::dungeon::{hurt_player, push_event, Dungeon};
+
+/// One stage of a boss fight, entered once the boss drops below `hp_percent`
+/// of its hp.
+pub struct Phase {
+    pub hp_percent: i32,
+    /// Added to the boss's attack for the rest of the fight.
+    pub attack_bonus: i32,
+    /// Wake every monster on the level and tell them where the player is.
+    pub roars: bool,
+    /// Turns between slams, which hit the player from two cells away for
+    /// double damage and stun them. `None` if the boss doesn't slam in this
+    /// phase.
+    pub slam_cooldown: Option<u32>,
+    /// Put on the boss for the rest of the fight.
+    pub status: Option<StatusKind>,
+    pub message: &'static str,
+}
+
+/// What makes a monster kind a boss. Each boss appears at most once a level.
+pub struct BossInfo {
+    pub kind: MonsterKind,
+    /// In order, the first starts the fight.
+    pub phases: [Phase; 3],
+    /// Always dropped by the boss when it dies.
+    pub drop: ItemKind,
+}
+
+const BOSS_TABLE: [BossInfo; 1] = [BossInfo {
+    kind: MonsterKind::OgreKing,
+    phases: [
+        Phase {
+            hp_percent: 100,
+            attack_bonus: 0,
+            roars: false,
+            slam_cooldown: None,
+            status: None,
+            message: "The ogre king lumbers towards you.",
+        },
+        Phase {
+            hp_percent: 60,
+            attack_bonus: 0,
+            roars: true,
+            slam_cooldown: Some(3),
+            status: None,
+            message: "The ogre king bellows for help!",
+        },
+        Phase {
+            hp_percent: 25,
+            attack_bonus: 30,
+            roars: false,
+            slam_cooldown: Some(2),
+            status: Some(StatusKind::Haste),
+            message: "The ogre king flies into a rage!",
+        },
+    ],
+    drop: ItemKind::Crown,
+}];
+
+pub fn boss_info(kind: MonsterKind) -> Option<&'static BossInfo> {
+    BOSS_TABLE.iter().find(|boss| boss.kind == kind)
+}
+
+/// The roster slot of the level's boss, if it is still alive.
+pub fn find_boss(d: &Dungeon) -> Option<usize> {
+    (0..d.monsters.len()).find(|&i| d.monsters[i].alive && boss_info(d.monsters[i].kind).is_some())
+}
+
+/// Put the boss asleep in the plain room cell furthest from the player, in
+/// a room of its own if there is one, see `spawn::place_monsters`. The boss
+/// is placed before anything but the vaults' monsters, and takes the place
+/// of one of those if the roster is full. Returns whether the boss was
+/// placed, which it isn't when no room cell is far enough from the player.
+pub fn place_boss(
+    mut d: Dungeon,
+    rooms: &[(usize, usize, usize, usize)],
+    min_player_distance: u16,
+) -> (Dungeon, bool) {
+    let slot = d
+        .monsters
+        .iter()
+        .position(|m| m.pos_x == UNPLACED)
+        .or_else(|| d.monsters.iter().position(|m| boss_info(m.kind).is_none()));
+    let Some(slot) = slot else {
+        return (d, false);
+    };
+    // Rooms that touch make one room, which may already have someone in it
+    let room_ids = spawn::room_ids(&d);
+    let taken = |room: usize| {
+        d.monsters
+            .iter()
+            .any(|m| m.pos_x != UNPLACED && room_ids[m.pos_x][m.pos_y] == room)
+    };
+    let free = |d: &Dungeon, x: usize, y: usize| {
+        d.terrain_map[x][y] == Terrain::Floor
+            && (x, y) != (d.player.position_x, d.player.position_y)
+            && d.distance_map[x][y] >= min_player_distance
+            && !d.monsters.iter().any(|m| m.pos_x == x && m.pos_y == y)
+            && !d
+                .items
+                .iter()
+                .flatten()
+                .any(|i| i.pos_x == x && i.pos_y == y)
+    };
+
+    // Room lists store (x, y, length, width), length running along y
+    let cell = rooms
+        .iter()
+        .flat_map(|&(x, y, length, width)| {
+            (x..x + width).flat_map(move |cx| (y..y + length).map(move |cy| (cx, cy)))
+        })
+        .filter(|&(x, y)| free(&d, x, y))
+        .max_by_key(|&(x, y)| {
+            let distance = d.distance_map[x][y];
+            (!taken(room_ids[x][y]), distance < IMPASSABLE, distance)
+        });
+
+    let Some((x, y)) = cell else {
+        return (d, false);
+    };
+    d.monsters[slot] = Monster::new(BOSS_TABLE[0].kind);
+    d.monsters[slot].pos_x = x;
+    d.monsters[slot].pos_y = y;
+    d.monsters[slot].awake = false;
+    (d, true)
+}
+
+/// Move every boss that has noticed the player into the phase its hp calls
+/// for, announcing it and roaring if the new phase says so. Phases only ever
+/// go forward.
+pub fn update_phases(mut d: Dungeon) -> Dungeon {
+    for i in 0..d.monsters.len() {
+        let monster = d.monsters[i];
+        let Some(boss) = boss_info(monster.kind) else {
+            continue;
+        };
+        if !monster.alive || !monster.awake {
+            continue;
+        }
+        let phase = boss
+            .phases
+            .iter()
+            .rposition(|p| monster.hp * 100 <= monster.max_hp * p.hp_percent)
+            .unwrap_or(0);
+        let noticed = monster.phase.is_some() || monster.last_seen.is_some();
+        if !noticed || monster.phase.is_some_and(|current| current >= phase) {
+            continue;
+        }
+
+        d.monsters[i].phase = Some(phase);
+        d.monsters[i].attack += boss.phases[phase].attack_bonus;
+        if let Some(kind) = boss.phases[phase].status {
+            d.monsters[i].statuses.add(Status {
+                kind,
+                turns: u32::MAX,
+                strength: 1,
+                source: None,
+            });
+        }
+        d = push_event(
+            d,
+            Event::BossPhase {
+                kind: monster.kind,
+                phase,
+            },
+        );
+        if boss.phases[phase].roars {
+            let player = (d.player.position_x, d.player.position_y);
+            for other in d.monsters.iter_mut().filter(|m| m.alive) {
+                other.awake = true;
+                other.last_seen = Some(player);
+            }
+        }
+    }
+    d
+}
+
+/// Let bosses with a slam ready use it, in place of their normal attack.
+/// Returns which monsters slammed.
+pub fn boss_slams(mut d: Dungeon) -> (Dungeon, Vec<usize>) {
+    let player = (d.player.position_x, d.player.position_y);
+    let mut slammed = Vec::new();
+    for i in 0..d.monsters.len() {
+        let monster = d.monsters[i];
+        let Some(boss) = boss_info(monster.kind) else {
+            continue;
+        };
+        if !monster.alive || !monster.awake {
+            continue;
+        }
+        d.monsters[i].cooldown = monster.cooldown.saturating_sub(1);
+        let Some(cooldown) = monster.phase.and_then(|p| boss.phases[p].slam_cooldown) else {
+            continue;
+        };
+        let reach = monster
+            .pos_x
+            .abs_diff(player.0)
+            .max(monster.pos_y.abs_diff(player.1));
+        if monster.cooldown == 0
+            && reach <= 2
+            && d.line_of_sight((monster.pos_x, monster.pos_y), player)
+        {
+            d = hurt_player(d, monster.kind, monster.attack * 2);
+            d.player.statuses.add(Status {
+                kind: StatusKind::Stun,
+                turns: 1,
+                strength: 1,
+                source: Some(monster.kind),
+            });
+            d.monsters[i].cooldown = cooldown;
+            slammed.push(i);
+        }
+    }
+    (d, slammed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ascii_map::load_map;
+
+    #[test]
+    fn boss_makes_room_on_a_full_roster() {
+        let map = "\
+%%%%%%%%%%%%%%%%
+%@gggggggggg...%
+%..............%
+%..............%
+%%%%%%%%%%%%%%%%
+";
+        let d = load_map(map).expect("boss test map loads");
+        let (d, placed) = place_boss(d, &[(1, 1, 14, 3)], 3);
+        assert!(placed);
+        let boss = find_boss(&d).expect("boss is on the roster");
+        assert_eq!((d.monsters[boss].pos_x, d.monsters[boss].pos_y), (3, 14));
+        let goblins = d
+            .monsters
+            .iter()
+            .filter(|m| m.alive && m.kind == MonsterKind::Goblin)
+            .count();
+        assert_eq!(goblins, 9);
+    }
+
+    #[test]
+    fn no_boss_too_close_to_the_player() {
+        let map = "\
+%%%%%
+%@..%
+%%%%%
+";
+        let d = load_map(map).expect("boss test map loads");
+        let (d, placed) = place_boss(d, &[(1, 1, 3, 1)], 3);
+        assert!(!placed);
+        assert!(find_boss(&d).is_none());
+    }
+
+    #[test]
+    fn boss_sleeps_anywhere_with_no_distance_to_keep() {
+        let map = "\
+%%%%%
+%@..%
+%%%%%
+";
+        let d = load_map(map).expect("boss test map loads");
+        let (d, placed) = place_boss(d, &[(1, 1, 3, 1)], 0);
+        assert!(placed);
+        let boss = find_boss(&d).expect("boss is on the roster");
+        assert_eq!((d.monsters[boss].pos_x, d.monsters[boss].pos_y), (1, 3));
+    }
+}
@@ -0,0 +1,339 @@
+use crate::dungeon::equipment::{self, SLOTS};
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::spell::{Spell, MAX_SPELLS};
+use crate::dungeon::Dungeon;
+
+/// Most talents a character can have, from their race and class together.
+pub const MAX_TALENTS: usize = 4;
+
+/// A lasting knack a character is born or trained with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Talent {
+    /// The player makes half as much noise.
+    Stealth,
+    /// The player gets hungry half as fast.
+    IronStomach,
+    /// The player spots hidden traps twice as often.
+    KeenEyes,
+}
+
+pub struct TalentInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+// One entry per `Talent` variant, in declaration order.
+const TALENT_TABLE: [TalentInfo; 3] = [
+    TalentInfo {
+        name: "Stealth",
+        description: "makes half as much noise",
+    },
+    TalentInfo {
+        name: "Iron stomach",
+        description: "gets hungry half as fast",
+    },
+    TalentInfo {
+        name: "Keen eyes",
+        description: "spots hidden traps twice as often",
+    },
+];
+
+impl Talent {
+    pub const ALL: [Talent; 3] = [Talent::Stealth, Talent::IronStomach, Talent::KeenEyes];
+
+    pub fn info(self) -> &'static TalentInfo {
+        &TALENT_TABLE[self as usize]
+    }
+
+    pub fn from_name(name: &str) -> Option<Talent> {
+        Talent::ALL
+            .iter()
+            .copied()
+            .find(|t| t.info().name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// A race or class from the definitions file. A character's stats are the
+/// sum of its race's and its class's.
+pub struct Definition {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub hp: i32,
+    pub attack: i32,
+    pub defence: i32,
+    pub regeneration: i32,
+    pub mana: i32,
+    pub items: Vec<ItemKind>,
+    pub talents: Vec<Talent>,
+    pub spells: Vec<Spell>,
+}
+
+pub struct Definitions {
+    pub races: Vec<Definition>,
+    pub classes: Vec<Definition>,
+}
+
+impl Definition {
+    /// One line summing the definition up, for choosing between them.
+    pub fn summary(&self) -> String {
+        let mut text = format!(
+            "{}: {} (hp {}, attack {}, defence {}",
+            self.name, self.description, self.hp, self.attack, self.defence
+        );
+        if !self.items.is_empty() {
+            let items: Vec<_> = self.items.iter().map(|i| i.name()).collect();
+            text.push_str(&format!("; {}", items.join(", ")));
+        }
+        for talent in self.talents.iter() {
+            text.push_str(&format!(
+                "; {}, {}",
+                talent.info().name,
+                talent.info().description
+            ));
+        }
+        if self.mana > 0 {
+            text.push_str(&format!("; mana {}", self.mana));
+        }
+        if !self.spells.is_empty() {
+            let spells: Vec<_> = self.spells.iter().map(|s| s.info().name).collect();
+            text.push_str(&format!("; casts {}", spells.join(", ")));
+        }
+        text.push(')');
+        text
+    }
+}
+
+/// The races and classes shipped in `static/characters.txt`.
+pub fn definitions() -> Definitions {
+    parse_definitions(include_str!("../../../static/characters.txt"))
+        .expect("Could not parse the character definitions.")
+}
+
+/// Definitions are blocks of `key: value` lines separated by blank lines,
+/// each starting with `race:` or `class:`. Lines starting with `;` are
+/// comments.
+pub fn parse_definitions(text: &'static str) -> Result<Definitions, String> {
+    let mut definitions = Definitions {
+        races: Vec::new(),
+        classes: Vec::new(),
+    };
+    let mut block: Vec<(&'static str, &'static str)> = Vec::new();
+
+    for line in text.lines().chain(std::iter::once("")) {
+        if line.starts_with(';') {
+            continue;
+        }
+        if line.trim().is_empty() {
+            if let Some(&(kind, name)) = block.first() {
+                let list = match kind {
+                    "race" => &mut definitions.races,
+                    "class" => &mut definitions.classes,
+                    _ => return Err(format!("'{}' is neither a race nor a class", kind)),
+                };
+                list.push(parse_definition(name, &block[1..])?);
+                block.clear();
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            return Err(format!("expected 'key: value', got '{}'", line));
+        };
+        block.push((key.trim(), value.trim()));
+    }
+    if definitions.races.is_empty() || definitions.classes.is_empty() {
+        return Err(String::from("at least one race and one class are needed"));
+    }
+    Ok(definitions)
+}
+
+/// Every key a definition may have after its `race:` or `class:` line.
+const KEYS: [&str; 9] = [
+    "description",
+    "hp",
+    "attack",
+    "defence",
+    "regeneration",
+    "mana",
+    "items",
+    "talents",
+    "spells",
+];
+
+fn parse_definition(
+    name: &'static str,
+    fields: &[(&'static str, &'static str)],
+) -> Result<Definition, String> {
+    if let Some((key, _)) = fields.iter().find(|(key, _)| !KEYS.contains(key)) {
+        return Err(format!("{} has unknown key '{}'", name, key));
+    }
+    let field = |key: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| *v)
+            .ok_or_else(|| format!("{} has no {}", name, key))
+    };
+    let number = |key: &str| {
+        field(key)?
+            .parse::<i32>()
+            .map_err(|_| format!("{}'s {} is not a number", name, key))
+    };
+    // Lists may be left out altogether
+    let list = |key: &str| {
+        field(key)
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .collect::<Vec<_>>()
+    };
+
+    let items = list("items")
+        .into_iter()
+        .map(|item| {
+            ItemKind::ALL
+                .iter()
+                .copied()
+                .find(|kind| kind.name().eq_ignore_ascii_case(item))
+                .ok_or_else(|| format!("{} starts with unknown item '{}'", name, item))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let talents = list("talents")
+        .into_iter()
+        .map(|talent| {
+            Talent::from_name(talent)
+                .ok_or_else(|| format!("{} has unknown talent '{}'", name, talent))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let spells = list("spells")
+        .into_iter()
+        .map(|spell| {
+            Spell::from_name(spell).ok_or_else(|| format!("{} has unknown spell '{}'", name, spell))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Definition {
+        name,
+        description: field("description")?,
+        hp: number("hp")?,
+        attack: number("attack")?,
+        defence: number("defence")?,
+        regeneration: number("regeneration")?,
+        mana: number("mana")?,
+        items,
+        talents,
+        spells,
+    })
+}
+
+/// Give the player the stats, starting pack, talents and spells of a race
+/// and class, wearing whatever gear they can. Whatever the player had before
+/// is replaced, and a talent or spell both give counts once.
+pub fn create_character(mut d: Dungeon, race: &Definition, class: &Definition) -> Dungeon {
+    let player = &mut d.player;
+    player.race = race.name;
+    player.class = class.name;
+    player.max_hp = (race.hp + class.hp).max(1);
+    player.hp = player.max_hp;
+    player.attack = (race.attack + class.attack).max(1);
+    player.defence = (race.defence + class.defence).max(1);
+    player.regeneration = (race.regeneration + class.regeneration).max(0);
+    player.max_mana = (race.mana + class.mana).max(0);
+    player.mana = player.max_mana;
+    player.inventory = [None; 10];
+    player.equipment = [None; SLOTS];
+    for &item in race.items.iter().chain(class.items.iter()) {
+        player.add_item(item);
+    }
+    equipment::wear_starting_gear(player);
+    player.talents = [None; MAX_TALENTS];
+    for &talent in race.talents.iter().chain(class.talents.iter()) {
+        if player.has_talent(talent) {
+            continue;
+        }
+        if let Some(slot) = player.talents.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(talent);
+        }
+    }
+    player.spells = [None; MAX_SPELLS];
+    for &spell in race.spells.iter().chain(class.spells.iter()) {
+        if player.spells.contains(&Some(spell)) {
+            continue;
+        }
+        if let Some(slot) = player.spells.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(spell);
+        }
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::empty_dungeon;
+
+    fn error(text: &'static str) -> Option<String> {
+        parse_definitions(text).err()
+    }
+
+    #[test]
+    fn shipped_definitions_parse() {
+        let definitions = parse_definitions(include_str!("../../../static/characters.txt"))
+            .expect("definitions parse");
+        assert!(!definitions.races.is_empty());
+        assert!(!definitions.classes.is_empty());
+    }
+
+    #[test]
+    fn bad_definitions_are_refused() {
+        assert_eq!(
+            error("race: Human\nstrength: 3\n").as_deref(),
+            Some("Human has unknown key 'strength'")
+        );
+        assert_eq!(
+            error("description: Plain.\nhp: 100\n").as_deref(),
+            Some("'description' is neither a race nor a class")
+        );
+        assert_eq!(
+            error("race: Human\nitems: Spoon\n").as_deref(),
+            Some("Human starts with unknown item 'Spoon'")
+        );
+        assert_eq!(
+            error("race: Human\ndescription: Plain.\nhp: lots\n").as_deref(),
+            Some("Human's hp is not a number")
+        );
+        assert_eq!(
+            error("race: Human\ndescription: Plain.\n").as_deref(),
+            Some("Human has no hp")
+        );
+    }
+
+    #[test]
+    fn race_and_class_add_up() {
+        let definitions = definitions();
+        let elf = definitions.races.iter().find(|r| r.name == "Elf").unwrap();
+        let rogue = definitions
+            .classes
+            .iter()
+            .find(|c| c.name == "Rogue")
+            .unwrap();
+        let player = create_character(empty_dungeon(), elf, rogue).player;
+        assert_eq!((player.race, player.class), ("Elf", "Rogue"));
+        assert_eq!(player.max_hp, elf.hp + rogue.hp);
+        assert_eq!(player.hp, player.max_hp);
+        assert_eq!(player.attack, elf.attack + rogue.attack);
+        assert_eq!(player.defence, elf.defence + rogue.defence);
+        assert_eq!(player.regeneration, elf.regeneration + rogue.regeneration);
+        assert_eq!(player.max_mana, elf.mana + rogue.mana);
+        for kind in [ItemKind::Darts, ItemKind::Pick, ItemKind::Ration] {
+            assert!(player.has_item(kind), "no {}", kind.name());
+        }
+        // Both give stealth, which counts once
+        assert_eq!(
+            player.talents,
+            [Some(Talent::Stealth), Some(Talent::KeenEyes), None, None]
+        );
+        assert_eq!(player.spells[0], Some(Spell::Blink));
+        assert!(player.spells[1..].iter().all(Option::is_none));
+    }
+}
@@ -0,0 +1,345 @@
+use crate::dungeon::event::Event;
+use crate::dungeon::item::{ItemClass, ItemKind};
+use crate::dungeon::status::{Status, StatusKind};
+use crate::dungeon::trap;
+use crate::dungeon::{free_floor_cell, pick_up_items, push_event, Dungeon};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// Mixed into the dungeon seed, so appearances don't follow the level layout.
+const APPEARANCE_SALT: u64 = 0x5eed_0a77;
+
+/// What unidentified potions look like, handed out afresh every game.
+const POTION_APPEARANCES: [&str; 8] = [
+    "murky", "bubbling", "golden", "smoky", "pink", "fizzy", "oily", "glowing",
+];
+
+/// What unidentified scrolls are labelled.
+const SCROLL_APPEARANCES: [&str; 8] = [
+    "ZELGO MER",
+    "FOOBIE BLETCH",
+    "XIXAXA",
+    "PRATYAVAYAH",
+    "ELBIB YLOH",
+    "VERR YED HORRE",
+    "KERNOD WEL",
+    "THARR",
+];
+
+/// What happens when a potion is drunk or a scroll read.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Effect {
+    /// Heal the player and cure poison.
+    Heal { amount: i32 },
+    /// Raise the player's attack for good.
+    Strength { attack: i32 },
+    /// Move the player to a random floor cell.
+    Teleport,
+    /// Show every monster on the level for the rest of it.
+    MagicMapping,
+    /// Frighten every monster in sight for some turns.
+    Fear { turns: u32 },
+    /// Identify everything the player is carrying.
+    Identify,
+}
+
+pub struct ConsumableInfo {
+    pub kind: ItemKind,
+    pub effect: Effect,
+    /// What the player is told once it has been used.
+    pub message: &'static str,
+}
+
+const CONSUMABLE_TABLE: [ConsumableInfo; 6] = [
+    ConsumableInfo {
+        kind: ItemKind::PotionOfHealing,
+        effect: Effect::Heal { amount: 40 },
+        message: "You feel much better.",
+    },
+    ConsumableInfo {
+        kind: ItemKind::PotionOfStrength,
+        effect: Effect::Strength { attack: 3 },
+        message: "You feel strong!",
+    },
+    ConsumableInfo {
+        kind: ItemKind::ScrollOfTeleport,
+        effect: Effect::Teleport,
+        message: "The world spins around you.",
+    },
+    ConsumableInfo {
+        kind: ItemKind::ScrollOfMagicMapping,
+        effect: Effect::MagicMapping,
+        message: "You sense everything that stirs on the level.",
+    },
+    ConsumableInfo {
+        kind: ItemKind::ScrollOfFear,
+        effect: Effect::Fear { turns: 10 },
+        message: "You hear a terrible shriek.",
+    },
+    ConsumableInfo {
+        kind: ItemKind::ScrollOfIdentify,
+        effect: Effect::Identify,
+        message: "You know more about your belongings.",
+    },
+];
+
+pub fn consumable_info(kind: ItemKind) -> Option<&'static ConsumableInfo> {
+    CONSUMABLE_TABLE.iter().find(|c| c.kind == kind)
+}
+
+/// What an item of a hidden class looks like in this game, the same for the
+/// same dungeon seed. `None` for items that are never hidden.
+pub fn appearance(seed: u64, kind: ItemKind) -> Option<&'static str> {
+    let mut appearances = match kind.class() {
+        ItemClass::Potion => POTION_APPEARANCES,
+        ItemClass::Scroll => SCROLL_APPEARANCES,
+        _ => return None,
+    };
+    appearances.shuffle(&mut StdRng::seed_from_u64(seed ^ APPEARANCE_SALT));
+    CONSUMABLE_TABLE
+        .iter()
+        .filter(|c| c.kind.class() == kind.class())
+        .position(|c| c.kind == kind)
+        .map(|i| appearances[i])
+}
+
+pub fn is_identified(d: &Dungeon, kind: ItemKind) -> bool {
+    d.identified[kind as usize] || appearance(d.seed, kind).is_none()
+}
+
+/// The name the player knows an item by.
+pub fn display_name(d: &Dungeon, kind: ItemKind) -> String {
+    if is_identified(d, kind) {
+        return String::from(kind.name());
+    }
+    match (kind.class(), appearance(d.seed, kind)) {
+        (ItemClass::Potion, Some(look)) => format!("{} potion", look),
+        (ItemClass::Scroll, Some(look)) => format!("scroll labelled {}", look),
+        _ => String::from(kind.name()),
+    }
+}
+
+/// Move the player to a random free floor cell, pick up whatever lies there
+/// and spring any trap hidden under it. With nowhere to go, the player stays
+/// put.
+pub fn teleport(mut d: Dungeon) -> Dungeon {
+    let mut rng = StdRng::seed_from_u64(d.seed ^ ((d.turn as u64) << 8) ^ 0x7e1e);
+    let Some((x, y)) = free_floor_cell(&d, &mut rng) else {
+        return d;
+    };
+    d.player.position_x = x;
+    d.player.position_y = y;
+    trap::enter_cell(pick_up_items(d))
+}
+
+/// Drink or read a carried potion or scroll, identifying its kind. Returns
+/// whether it was used.
+pub fn use_item(mut d: Dungeon, kind: ItemKind) -> (Dungeon, bool) {
+    let Some(info) = consumable_info(kind) else {
+        return (d, false);
+    };
+    if !d.player.alive || !d.player.remove_item(kind) {
+        return (d, false);
+    }
+    let appearance = if is_identified(&d, kind) {
+        None
+    } else {
+        appearance(d.seed, kind)
+    };
+    d.identified[kind as usize] = true;
+    d = push_event(
+        d,
+        Event::ItemUsed {
+            item: kind,
+            appearance,
+        },
+    );
+
+    match info.effect {
+        Effect::Heal { amount } => {
+            d.player.hp = (d.player.hp + amount).min(d.player.max_hp);
+            d.player.statuses.remove(StatusKind::Poison);
+        }
+        Effect::Strength { attack } => d.player.attack += attack,
+        Effect::Teleport => d = teleport(d),
+        Effect::MagicMapping => d.mapped = true,
+        Effect::Fear { turns } => {
+            let player = (d.player.position_x, d.player.position_y);
+            for i in 0..d.monsters.len() {
+                let monster = d.monsters[i];
+                if monster.alive && d.line_of_sight(player, (monster.pos_x, monster.pos_y)) {
+                    d.monsters[i].statuses.add(Status {
+                        kind: StatusKind::Fear,
+                        turns,
+                        strength: 1,
+                        source: None,
+                    });
+                    d.monsters[i].awake = true;
+                    d.monsters[i].last_seen = Some(player);
+                }
+            }
+        }
+        Effect::Identify => {
+            for item in d.player.inventory.iter().flatten() {
+                d.identified[*item as usize] = true;
+            }
+        }
+    }
+    (d, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ascii_map::load_map;
+    use crate::dungeon::terrain::Terrain;
+
+    const ROOM: &str = "\
+%%%%%%%%%%
+%@..g.%.r%
+%.....%..%
+%%%%%%%%%%
+";
+
+    /// `ROOM` with the player carrying one of an item, known by its
+    /// appearance only.
+    fn carrying(kind: ItemKind) -> Dungeon {
+        let mut d = load_map(ROOM).expect("consumable test map loads");
+        d.seed = 7;
+        assert!(d.player.add_item(kind));
+        d
+    }
+
+    fn used(kind: ItemKind) -> Dungeon {
+        let (d, used) = use_item(carrying(kind), kind);
+        assert!(used);
+        assert!(!d.player.has_item(kind));
+        d
+    }
+
+    fn appearances(seed: u64, class: ItemClass) -> Vec<&'static str> {
+        CONSUMABLE_TABLE
+            .iter()
+            .filter(|c| c.kind.class() == class)
+            .map(|c| appearance(seed, c.kind).expect("hidden until used"))
+            .collect()
+    }
+
+    #[test]
+    fn appearances_are_dealt_out_by_the_seed() {
+        for (class, names) in [
+            (ItemClass::Potion, POTION_APPEARANCES),
+            (ItemClass::Scroll, SCROLL_APPEARANCES),
+        ] {
+            let looks = appearances(1, class);
+            assert!(looks.iter().all(|look| names.contains(look)));
+            let mut distinct = looks.clone();
+            distinct.sort();
+            distinct.dedup();
+            assert_eq!(distinct.len(), looks.len());
+            assert_eq!(appearances(1, class), looks);
+            assert!((2..20).any(|seed| appearances(seed, class) != looks));
+        }
+        assert_eq!(appearance(1, ItemKind::Sword), None);
+    }
+
+    #[test]
+    fn using_an_item_identifies_its_kind() {
+        let kind = ItemKind::PotionOfStrength;
+        let d = carrying(kind);
+        assert!(!is_identified(&d, kind));
+        let look = appearance(d.seed, kind);
+        assert_eq!(display_name(&d, kind), format!("{} potion", look.unwrap()));
+        let (d, _) = use_item(d, kind);
+        assert!(is_identified(&d, kind));
+        assert_eq!(display_name(&d, kind), kind.name());
+        assert!(d.events.iter().flatten().any(|e| matches!(
+            e,
+            Event::ItemUsed { item, appearance } if *item == kind && *appearance == look
+        )));
+    }
+
+    #[test]
+    fn healing_cures_poison_up_to_full_health() {
+        let mut d = carrying(ItemKind::PotionOfHealing);
+        d.player.hp = d.player.max_hp - 10;
+        d.player.statuses.add(Status {
+            kind: StatusKind::Poison,
+            turns: 5,
+            strength: 1,
+            source: None,
+        });
+        let (d, _) = use_item(d, ItemKind::PotionOfHealing);
+        assert_eq!(d.player.hp, d.player.max_hp);
+        assert!(!d.player.statuses.has(StatusKind::Poison));
+    }
+
+    #[test]
+    fn strength_raises_attack() {
+        let before = carrying(ItemKind::PotionOfStrength).player.attack;
+        assert_eq!(used(ItemKind::PotionOfStrength).player.attack, before + 3);
+    }
+
+    #[test]
+    fn teleport_lands_on_a_free_floor_cell() {
+        for turn in 0..20 {
+            let mut d = carrying(ItemKind::ScrollOfTeleport);
+            d.turn = turn;
+            let (d, _) = use_item(d, ItemKind::ScrollOfTeleport);
+            let (x, y) = (d.player.position_x, d.player.position_y);
+            assert_ne!((x, y), (1, 1));
+            assert_eq!(d.terrain_map[x][y], Terrain::Floor);
+            assert!(!d
+                .monsters
+                .iter()
+                .any(|m| m.alive && (m.pos_x, m.pos_y) == (x, y)));
+        }
+    }
+
+    #[test]
+    fn teleport_with_nowhere_to_go_stays_put() {
+        let mut d = load_map(
+            "\
+%%%%%%
+%@###%
+%%%%%%
+",
+        )
+        .expect("consumable test map loads");
+        assert!(d.player.add_item(ItemKind::ScrollOfTeleport));
+        let (d, used) = use_item(d, ItemKind::ScrollOfTeleport);
+        assert!(used);
+        assert_eq!((d.player.position_x, d.player.position_y), (1, 1));
+    }
+
+    #[test]
+    fn magic_mapping_shows_the_monsters() {
+        assert!(!carrying(ItemKind::ScrollOfMagicMapping).mapped);
+        assert!(used(ItemKind::ScrollOfMagicMapping).mapped);
+    }
+
+    #[test]
+    fn fear_frightens_only_monsters_in_sight() {
+        let d = used(ItemKind::ScrollOfFear);
+        let feared = |kind: char| {
+            d.monsters
+                .iter()
+                .find(|m| m.alive && m.kind.info().character == kind)
+                .expect("monster on the map")
+                .statuses
+                .has(StatusKind::Fear)
+        };
+        assert!(feared('g'));
+        assert!(!feared('r'));
+    }
+
+    #[test]
+    fn identify_names_everything_carried() {
+        let mut d = carrying(ItemKind::ScrollOfIdentify);
+        assert!(d.player.add_item(ItemKind::PotionOfHealing));
+        let (d, _) = use_item(d, ItemKind::ScrollOfIdentify);
+        assert!(is_identified(&d, ItemKind::PotionOfHealing));
+        assert!(!is_identified(&d, ItemKind::ScrollOfFear));
+    }
+}
@@ -0,0 +1,291 @@
+use crate::dungeon::event::Event;
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::player::Player;
+use crate::dungeon::{push_event, Dungeon};
+
+/// How many equipment slots the player has, one per `Slot` variant.
+pub const SLOTS: usize = 6;
+
+/// Where on the player a piece of gear is worn or wielded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Slot {
+    Weapon,
+    Offhand,
+    Body,
+    Head,
+    Ring,
+    Amulet,
+}
+
+pub struct SlotInfo {
+    pub name: &'static str,
+}
+
+// One entry per `Slot` variant, in declaration order.
+const SLOT_TABLE: [SlotInfo; SLOTS] = [
+    SlotInfo { name: "Weapon" },
+    SlotInfo { name: "Offhand" },
+    SlotInfo { name: "Body" },
+    SlotInfo { name: "Head" },
+    SlotInfo { name: "Ring" },
+    SlotInfo { name: "Amulet" },
+];
+
+impl Slot {
+    pub const ALL: [Slot; SLOTS] = [
+        Slot::Weapon,
+        Slot::Offhand,
+        Slot::Body,
+        Slot::Head,
+        Slot::Ring,
+        Slot::Amulet,
+    ];
+
+    pub fn info(self) -> &'static SlotInfo {
+        &SLOT_TABLE[self as usize]
+    }
+}
+
+/// What the player needs before they can put a piece of gear on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Requirement {
+    /// At least this experience level.
+    Level(u32),
+    /// At least this much attack of their own, not counting gear or hunger.
+    Attack(i32),
+}
+
+pub struct GearInfo {
+    pub kind: ItemKind,
+    pub slot: Slot,
+    /// Added to the player's attack and defence while equipped.
+    pub attack: i32,
+    pub defence: i32,
+    pub requirement: Option<Requirement>,
+}
+
+const GEAR_TABLE: [GearInfo; 8] = [
+    GearInfo {
+        kind: ItemKind::Sword,
+        slot: Slot::Weapon,
+        attack: 3,
+        defence: 0,
+        requirement: None,
+    },
+    GearInfo {
+        kind: ItemKind::Axe,
+        slot: Slot::Weapon,
+        attack: 6,
+        defence: 0,
+        requirement: Some(Requirement::Attack(13)),
+    },
+    GearInfo {
+        kind: ItemKind::Shield,
+        slot: Slot::Offhand,
+        attack: 0,
+        defence: 2,
+        requirement: None,
+    },
+    GearInfo {
+        kind: ItemKind::LeatherArmour,
+        slot: Slot::Body,
+        attack: 0,
+        defence: 2,
+        requirement: None,
+    },
+    GearInfo {
+        kind: ItemKind::ChainMail,
+        slot: Slot::Body,
+        attack: 0,
+        defence: 4,
+        requirement: Some(Requirement::Level(3)),
+    },
+    GearInfo {
+        kind: ItemKind::Helmet,
+        slot: Slot::Head,
+        attack: 0,
+        defence: 1,
+        requirement: None,
+    },
+    GearInfo {
+        kind: ItemKind::RingOfMight,
+        slot: Slot::Ring,
+        attack: 2,
+        defence: 0,
+        requirement: Some(Requirement::Level(2)),
+    },
+    GearInfo {
+        kind: ItemKind::AmuletOfWarding,
+        slot: Slot::Amulet,
+        attack: 0,
+        defence: 2,
+        requirement: None,
+    },
+];
+
+pub fn gear_info(kind: ItemKind) -> Option<&'static GearInfo> {
+    GEAR_TABLE.iter().find(|g| g.kind == kind)
+}
+
+/// The requirement keeping the player from equipping an item, if any.
+pub fn unmet_requirement(player: &Player, kind: ItemKind) -> Option<Requirement> {
+    let requirement = gear_info(kind)?.requirement?;
+    let met = match requirement {
+        Requirement::Level(level) => player.level >= level,
+        Requirement::Attack(attack) => player.attack >= attack,
+    };
+    (!met).then_some(requirement)
+}
+
+/// The player as they would be with an item equipped in place of whatever
+/// is in its slot, requirements aside. For comparing gear.
+pub fn try_on(mut player: Player, kind: ItemKind) -> Player {
+    if let Some(info) = gear_info(kind) {
+        player.equipment[info.slot as usize] = Some(kind);
+    }
+    player
+}
+
+/// The player as they would be with a slot emptied.
+pub fn take_off(mut player: Player, slot: Slot) -> Player {
+    player.equipment[slot as usize] = None;
+    player
+}
+
+/// Equip a carried item, putting whatever was in its slot back in the pack.
+/// Returns whether it was equipped; the player is told when they don't meet
+/// its requirement.
+pub fn equip(mut d: Dungeon, kind: ItemKind) -> (Dungeon, bool) {
+    let Some(info) = gear_info(kind) else {
+        return (d, false);
+    };
+    if !d.player.alive || !d.player.has_item(kind) {
+        return (d, false);
+    }
+    if let Some(requirement) = unmet_requirement(&d.player, kind) {
+        return (
+            push_event(
+                d,
+                Event::RequirementNotMet {
+                    item: kind,
+                    requirement,
+                },
+            ),
+            false,
+        );
+    }
+    // Taking the item out leaves room for the one it replaces
+    d.player.remove_item(kind);
+    if let Some(old) = d.player.equipment[info.slot as usize].replace(kind) {
+        d.player.add_item(old);
+    }
+    (push_event(d, Event::Equipped { item: kind }), true)
+}
+
+/// Put whatever is in a slot back in the pack. Returns whether there was
+/// anything to take off and room for it.
+pub fn unequip(mut d: Dungeon, slot: Slot) -> (Dungeon, bool) {
+    let Some(item) = d.player.equipment[slot as usize] else {
+        return (d, false);
+    };
+    if !d.player.alive {
+        return (d, false);
+    }
+    if !d.player.add_item(item) {
+        return (push_event(d, Event::PackFull), false);
+    }
+    d.player.equipment[slot as usize] = None;
+    (push_event(d, Event::Unequipped { item }), true)
+}
+
+/// Equip carried gear into empty slots, without a word or a turn, for a
+/// character's starting pack.
+pub fn wear_starting_gear(player: &mut Player) {
+    let carried: Vec<ItemKind> = player.inventory.iter().flatten().copied().collect();
+    for kind in carried {
+        let Some(info) = gear_info(kind) else {
+            continue;
+        };
+        let free = player.equipment[info.slot as usize].is_none();
+        if free && unmet_requirement(player, kind).is_none() {
+            player.remove_item(kind);
+            player.equipment[info.slot as usize] = Some(kind);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::empty_dungeon;
+
+    /// A player carrying the given items, wearing nothing.
+    fn carrying(items: &[ItemKind]) -> Dungeon {
+        let mut d = empty_dungeon();
+        d.player.inventory = [None; 10];
+        d.player.equipment = [None; SLOTS];
+        for &item in items {
+            assert!(d.player.add_item(item));
+        }
+        d
+    }
+
+    #[test]
+    fn equipping_swaps_out_what_was_worn() {
+        let mut d = carrying(&[ItemKind::Axe]);
+        d.player.attack = 13;
+        d.player.equipment[Slot::Weapon as usize] = Some(ItemKind::Sword);
+        let (d, equipped) = equip(d, ItemKind::Axe);
+        assert!(equipped);
+        assert_eq!(
+            d.player.equipment[Slot::Weapon as usize],
+            Some(ItemKind::Axe)
+        );
+        assert!(d.player.has_item(ItemKind::Sword));
+        assert!(!d.player.has_item(ItemKind::Axe));
+    }
+
+    #[test]
+    fn unmet_requirement_refuses_the_item() {
+        let (d, equipped) = equip(carrying(&[ItemKind::ChainMail]), ItemKind::ChainMail);
+        assert!(!equipped);
+        assert_eq!(d.player.equipment[Slot::Body as usize], None);
+        assert!(d.player.has_item(ItemKind::ChainMail));
+        assert!(d.events.iter().flatten().any(|e| matches!(
+            e,
+            Event::RequirementNotMet {
+                requirement: Requirement::Level(3),
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn unequipping_into_a_full_pack_fails() {
+        let mut d = carrying(&[ItemKind::Ration; 10]);
+        d.player.equipment[Slot::Weapon as usize] = Some(ItemKind::Sword);
+        let (d, unequipped) = unequip(d, Slot::Weapon);
+        assert!(!unequipped);
+        assert_eq!(
+            d.player.equipment[Slot::Weapon as usize],
+            Some(ItemKind::Sword)
+        );
+        assert!(d
+            .events
+            .iter()
+            .flatten()
+            .any(|e| matches!(e, Event::PackFull)));
+    }
+
+    #[test]
+    fn gear_adds_to_attack_and_defence() {
+        let d = carrying(&[ItemKind::Sword, ItemKind::Shield]);
+        let (attack, defence) = (d.player.attack_power(), d.player.defence_power());
+        let (d, _) = equip(d, ItemKind::Sword);
+        let (d, _) = equip(d, ItemKind::Shield);
+        assert_eq!(d.player.attack_power(), attack + 3);
+        assert_eq!(d.player.defence_power(), defence + 2);
+        let (d, _) = unequip(d, Slot::Weapon);
+        assert_eq!(d.player.attack_power(), attack);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::dungeon::equipment::Requirement;
+use crate::dungeon::hunger::Hunger;
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::monster::MonsterKind;
+use crate::dungeon::quest::Objective;
+use crate::dungeon::spell::Spell;
+use crate::dungeon::status::StatusKind;
+use crate::dungeon::trap::TrapKind;
+
+/// Something that happened in the dungeon that the front-end may want to
+/// report or react to.
+#[derive(Copy, Clone, Debug)]
+pub enum Event {
+    MonsterDied {
+        kind: MonsterKind,
+        pos_x: usize,
+        pos_y: usize,
+    },
+    MonsterFled {
+        kind: MonsterKind,
+        pos_x: usize,
+        pos_y: usize,
+    },
+    PlayerHit {
+        kind: MonsterKind,
+        damage: i32,
+    },
+    PlayerDied {
+        kind: MonsterKind,
+    },
+    BossPhase {
+        kind: MonsterKind,
+        phase: usize,
+    },
+    MonsterSpat {
+        kind: MonsterKind,
+    },
+    MonsterSummoned {
+        kind: MonsterKind,
+        summoned: MonsterKind,
+    },
+    ItemStolen {
+        kind: MonsterKind,
+        item: ItemKind,
+    },
+    PlayerPoisoned {
+        kind: MonsterKind,
+    },
+    LevelUp {
+        level: u32,
+    },
+    /// One of the player's status effects wore off.
+    StatusEnded {
+        kind: StatusKind,
+    },
+    /// The player's hunger moved to a new state.
+    HungerChanged {
+        hunger: Hunger,
+    },
+    Ate {
+        item: ItemKind,
+    },
+    PlayerFainted,
+    PlayerStarved,
+    /// Poison that no monster is to blame for killed the player.
+    PoisonedToDeath,
+    SpellCast {
+        spell: Spell,
+    },
+    /// A spell hurt a monster, or only affected it when `damage` is 0.
+    SpellHit {
+        spell: Spell,
+        kind: MonsterKind,
+        damage: i32,
+    },
+    /// A spell's target was out of range or unsuitable, and nothing was spent.
+    SpellFizzled {
+        spell: Spell,
+    },
+    NotEnoughMana {
+        spell: Spell,
+    },
+    /// The player drank or read an item, which was unidentified if it has an
+    /// appearance.
+    ItemUsed {
+        item: ItemKind,
+        appearance: Option<&'static str>,
+    },
+    Equipped {
+        item: ItemKind,
+    },
+    Unequipped {
+        item: ItemKind,
+    },
+    /// The player tried to equip something they aren't ready for.
+    RequirementNotMet {
+        item: ItemKind,
+        requirement: Requirement,
+    },
+    /// There was no room in the pack for an item.
+    PackFull,
+    GoldPickedUp {
+        amount: u32,
+    },
+    /// The player picked up goods in a shop, to be paid for before leaving.
+    Unpaid {
+        item: ItemKind,
+        price: u32,
+    },
+    Bought {
+        item: ItemKind,
+        price: u32,
+    },
+    Sold {
+        item: ItemKind,
+        price: u32,
+    },
+    /// Unpaid goods went back on the shop floor.
+    PutBack {
+        item: ItemKind,
+    },
+    Paid {
+        amount: u32,
+    },
+    CantAfford {
+        price: u32,
+    },
+    /// The shop won't buy an item that is worth nothing.
+    NotInterested {
+        item: ItemKind,
+    },
+    /// No floor left in the shop for an item sold to it.
+    ShopFull,
+    LeftWithoutPaying {
+        debt: u32,
+    },
+    /// A peaceful monster was attacked or robbed, and turned hostile.
+    MonsterProvoked {
+        kind: MonsterKind,
+    },
+    /// The player stepped on a trap and lost this many hp to it.
+    TrapSprung {
+        kind: TrapKind,
+        damage: i32,
+    },
+    KilledByTrap {
+        kind: TrapKind,
+    },
+    /// A monster in sight stepped on a trap.
+    MonsterSprungTrap {
+        kind: MonsterKind,
+        trap: TrapKind,
+    },
+    TrapFound {
+        kind: TrapKind,
+    },
+    /// A search turned up nothing.
+    NothingFound,
+    /// The player arrived on a deeper level.
+    Descended {
+        depth: u32,
+    },
+    Ascended {
+        depth: u32,
+    },
+    /// The player reached the surface without finishing their quest.
+    QuestUnfinished {
+        objective: Objective,
+    },
+    /// The player finished their quest and won the game.
+    Victory,
+    /// The player rested this many turns and is back to full health and mana.
+    Rested {
+        turns: u32,
+    },
+    /// Resting was refused, as the player is too hungry to heal.
+    TooHungryToRest,
+    /// Resting was refused, as poison stops the player healing.
+    PoisonedCantRest,
+    /// Resting stopped early, for the monster in view, or `None` when
+    /// something hurt the player.
+    RestInterrupted {
+        kind: Option<MonsterKind>,
+    },
+}
@@ -0,0 +1,173 @@
+use crate::dungeon::event::Event;
+use crate::dungeon::{push_event, Dungeon};
+
+/// Total xp needed to reach each level after the first. The player stops
+/// levelling at the end of the table.
+const LEVEL_XP: [u32; 9] = [20, 50, 100, 170, 260, 380, 530, 720, 950];
+
+/// Stats gained on every level up.
+const HP_PER_LEVEL: i32 = 10;
+const ATTACK_PER_LEVEL: i32 = 2;
+const DEFENCE_PER_LEVEL: i32 = 1;
+
+/// A bonus the player may pick on top of the usual growth when levelling up.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Perk {
+    Toughness,
+    Might,
+    Guard,
+}
+
+pub struct PerkInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub max_hp: i32,
+    pub attack: i32,
+    pub defence: i32,
+}
+
+// One entry per `Perk` variant, in declaration order.
+const PERK_TABLE: [PerkInfo; 3] = [
+    PerkInfo {
+        name: "Toughness",
+        description: "+20 max hp",
+        max_hp: 20,
+        attack: 0,
+        defence: 0,
+    },
+    PerkInfo {
+        name: "Might",
+        description: "+5 attack",
+        max_hp: 0,
+        attack: 5,
+        defence: 0,
+    },
+    PerkInfo {
+        name: "Guard",
+        description: "+3 defence",
+        max_hp: 0,
+        attack: 0,
+        defence: 3,
+    },
+];
+
+impl Perk {
+    pub const ALL: [Perk; 3] = [Perk::Toughness, Perk::Might, Perk::Guard];
+
+    pub fn info(self) -> &'static PerkInfo {
+        &PERK_TABLE[self as usize]
+    }
+}
+
+/// Total xp needed for the level after `level`, `None` at the top level.
+pub fn next_level_xp(level: u32) -> Option<u32> {
+    match level {
+        0 => Some(0),
+        _ => LEVEL_XP.get(level as usize - 1).copied(),
+    }
+}
+
+/// Give the player xp, levelling up as many times as it is enough for. Each
+/// level raises the player's stats, heals the gained hp and earns a perk to
+/// spend with `choose_perk`.
+pub fn gain_xp(mut d: Dungeon, xp: u32) -> Dungeon {
+    d.player.xp += xp;
+    while let Some(needed) = next_level_xp(d.player.level) {
+        if d.player.xp < needed {
+            break;
+        }
+        d.player.level += 1;
+        d.player.max_hp += HP_PER_LEVEL;
+        d.player.hp += HP_PER_LEVEL;
+        d.player.attack += ATTACK_PER_LEVEL;
+        d.player.defence += DEFENCE_PER_LEVEL;
+        d.player.perks += 1;
+        d = push_event(
+            d,
+            Event::LevelUp {
+                level: d.player.level,
+            },
+        );
+    }
+    d
+}
+
+/// Spend an unspent perk, if the player has one.
+pub fn choose_perk(mut d: Dungeon, perk: Perk) -> Dungeon {
+    if d.player.perks == 0 {
+        return d;
+    }
+    let info = perk.info();
+    d.player.perks -= 1;
+    d.player.max_hp += info.max_hp;
+    d.player.hp += info.max_hp;
+    d.player.attack += info.attack;
+    d.player.defence += info.defence;
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::empty_dungeon;
+
+    fn level_ups(d: &Dungeon) -> Vec<u32> {
+        d.events
+            .iter()
+            .flatten()
+            .filter_map(|e| match e {
+                Event::LevelUp { level } => Some(*level),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn crossing_a_threshold_levels_up_once() {
+        let d = gain_xp(empty_dungeon(), LEVEL_XP[0] - 1);
+        assert_eq!(d.player.level, 1);
+        let d = gain_xp(d, 1);
+        assert_eq!((d.player.level, d.player.perks), (2, 1));
+        assert_eq!(level_ups(&d), [2]);
+    }
+
+    #[test]
+    fn one_kill_can_give_several_levels() {
+        let d = gain_xp(empty_dungeon(), LEVEL_XP[1]);
+        assert_eq!((d.player.level, d.player.perks), (3, 2));
+        assert_eq!(level_ups(&d), [2, 3]);
+    }
+
+    #[test]
+    fn levelling_stops_at_the_top() {
+        let d = gain_xp(empty_dungeon(), 100_000);
+        let top = LEVEL_XP.len() as u32 + 1;
+        assert_eq!(d.player.level, top);
+        assert_eq!(next_level_xp(top), None);
+        let d = gain_xp(d, 100_000);
+        assert_eq!(d.player.level, top);
+    }
+
+    #[test]
+    fn every_level_grows_the_stats() {
+        let before = empty_dungeon().player;
+        let after = gain_xp(empty_dungeon(), LEVEL_XP[1]).player;
+        assert_eq!(after.max_hp, before.max_hp + 2 * HP_PER_LEVEL);
+        assert_eq!(after.hp, before.hp + 2 * HP_PER_LEVEL);
+        assert_eq!(after.attack, before.attack + 2 * ATTACK_PER_LEVEL);
+        assert_eq!(after.defence, before.defence + 2 * DEFENCE_PER_LEVEL);
+    }
+
+    #[test]
+    fn a_perk_is_spent_once() {
+        let d = gain_xp(empty_dungeon(), LEVEL_XP[0]);
+        let before = d.player;
+        let d = choose_perk(d, Perk::Might);
+        assert_eq!(d.player.perks, 0);
+        assert_eq!(d.player.attack, before.attack + Perk::Might.info().attack);
+        let d = choose_perk(d, Perk::Might);
+        assert_eq!(d.player.attack, before.attack + Perk::Might.info().attack);
+        let d = choose_perk(d, Perk::Toughness);
+        assert_eq!(d.player.max_hp, before.max_hp);
+    }
+}
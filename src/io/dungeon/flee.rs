@@ -0,0 +1,82 @@
+use crate::dungeon::event::Event;
+use crate::dungeon::status::StatusKind;
+use crate::dungeon::terrain::{Terrain, IMPASSABLE};
+use crate::dungeon::{neighbourhood, push_event, Dungeon};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Cells the safety map can't reach.
+pub const UNSAFE: i32 = i32::MAX;
+
+/// The distance map is multiplied by this (as a fraction of 10) to make the
+/// safety map. Going past -10 makes fleeing monsters willing to run a little
+/// towards the player to get out of a dead end.
+const FLEE_SCALE: i32 = -12;
+
+/// Map of how safe each cell is from the player, lower is safer, for
+/// monsters that can open doors or not.
+///
+/// Built by negating and scaling the distance map, then relaxing it again
+/// with Dijkstra so every cell flows downhill towards the far, open parts of
+/// the level rather than into the nearest corner. Closed doors are walls to
+/// monsters that can't open them, so they aren't led up to one.
+pub fn safety_map(d: &Dungeon, opens_doors: bool) -> [[i32; 80]; 30] {
+    let mut map = [[UNSAFE; 80]; 30];
+    let mut heap = BinaryHeap::new();
+    let blocked = |terrain: Terrain| !opens_doors && terrain.opens_to().is_some();
+
+    for (x, row) in map.iter_mut().enumerate().take(d.dungeon_x) {
+        for (y, cell) in row.iter_mut().enumerate().take(d.dungeon_y) {
+            if d.distance_map[x][y] < IMPASSABLE && !blocked(d.terrain_map[x][y]) {
+                *cell = d.distance_map[x][y] as i32 * FLEE_SCALE / 10;
+                heap.push(Reverse((*cell, x, y)));
+            }
+        }
+    }
+
+    while let Some(Reverse((cost, x, y))) = heap.pop() {
+        if cost > map[x][y] {
+            continue;
+        }
+        for (nx, ny) in neighbourhood(x, y) {
+            let terrain = d.terrain_map[nx][ny];
+            let step = terrain.movement_cost();
+            if step == IMPASSABLE || blocked(terrain) {
+                continue;
+            }
+            let next = cost + step as i32;
+            if next < map[nx][ny] {
+                map[nx][ny] = next;
+                heap.push(Reverse((next, nx, ny)));
+            }
+        }
+    }
+    map
+}
+
+/// Check every monster's nerve. Monsters whose hp drop below their morale
+/// turn and run, as do thieves with their loot and anything made afraid, and
+/// are reported when they do.
+pub fn update_morale(mut d: Dungeon) -> Dungeon {
+    for i in 0..d.monsters.len() {
+        let monster = d.monsters[i];
+        if !monster.alive {
+            continue;
+        }
+        let frightened = monster.hp * 100 < monster.max_hp * monster.kind.info().morale
+            || monster.carrying.is_some()
+            || monster.statuses.has(StatusKind::Fear);
+        if frightened && !monster.fleeing {
+            d = push_event(
+                d,
+                Event::MonsterFled {
+                    kind: monster.kind,
+                    pos_x: monster.pos_x,
+                    pos_y: monster.pos_y,
+                },
+            );
+        }
+        d.monsters[i].fleeing = frightened;
+    }
+    d
+}
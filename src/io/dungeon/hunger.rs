@@ -0,0 +1,306 @@
+use crate::dungeon::character::Talent;
+use crate::dungeon::event::Event;
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::status::{Status, StatusKind};
+use crate::dungeon::{push_event, Dungeon};
+use quicksilver::prelude::Color;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Nutrition the player starts the game with.
+pub const STARTING_NUTRITION: u32 = 1200;
+/// The most the player can eat; anything past it is wasted.
+pub const MAX_NUTRITION: u32 = 2000;
+
+/// Chance a turn that a fainting player passes out, and for how long.
+const FAINT_CHANCE: f64 = 0.1;
+const FAINT_TURNS: u32 = 3;
+/// Hp lost each turn with nothing left in the stomach.
+const STARVE_DAMAGE: i32 = 1;
+
+/// How full the player is, from best to worst.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Hunger {
+    Satiated,
+    Fed,
+    Hungry,
+    Weak,
+    /// Now and then passes out, and starves at no nutrition at all.
+    Fainting,
+}
+
+pub struct HungerInfo {
+    pub name: &'static str,
+    /// Short label for the status panel, empty when there's nothing to say.
+    pub label: &'static str,
+    pub color: Color,
+    /// The least nutrition the player can have in this state.
+    pub min_nutrition: u32,
+    /// Added to the player's attack and defence.
+    pub attack: i32,
+    pub defence: i32,
+    /// Whether the player regenerates hp.
+    pub heals: bool,
+}
+
+// One entry per `Hunger` variant, in declaration order.
+const HUNGER_TABLE: [HungerInfo; 5] = [
+    HungerInfo {
+        name: "satiated",
+        label: "Satiated",
+        color: Color::GREEN,
+        min_nutrition: 1600,
+        attack: 0,
+        defence: 0,
+        heals: true,
+    },
+    HungerInfo {
+        name: "fed",
+        label: "",
+        color: Color::BLACK,
+        min_nutrition: 300,
+        attack: 0,
+        defence: 0,
+        heals: true,
+    },
+    HungerInfo {
+        name: "hungry",
+        label: "Hungry",
+        color: Color::ORANGE,
+        min_nutrition: 100,
+        attack: -2,
+        defence: 0,
+        heals: true,
+    },
+    HungerInfo {
+        name: "weak",
+        label: "Weak",
+        color: Color::RED,
+        min_nutrition: 30,
+        attack: -5,
+        defence: -3,
+        heals: false,
+    },
+    HungerInfo {
+        name: "fainting",
+        label: "Fainting",
+        color: Color::RED,
+        min_nutrition: 0,
+        attack: -8,
+        defence: -5,
+        heals: false,
+    },
+];
+
+impl Hunger {
+    pub const ALL: [Hunger; 5] = [
+        Hunger::Satiated,
+        Hunger::Fed,
+        Hunger::Hungry,
+        Hunger::Weak,
+        Hunger::Fainting,
+    ];
+
+    pub fn info(self) -> &'static HungerInfo {
+        &HUNGER_TABLE[self as usize]
+    }
+
+    /// The state the player is in with this much nutrition.
+    pub fn of(nutrition: u32) -> Hunger {
+        Hunger::ALL
+            .iter()
+            .copied()
+            .find(|h| nutrition >= h.info().min_nutrition)
+            .unwrap_or(Hunger::Fainting)
+    }
+}
+
+/// A turn's worth of digestion, every other turn for an iron stomach. The
+/// player is told when their hunger gets worse; fainting players may pass
+/// out, and empty ones starve.
+pub fn tick_hunger(mut d: Dungeon) -> Dungeon {
+    let slow = d.player.has_talent(Talent::IronStomach) && d.turn % 2 == 1;
+    if !d.player.alive || slow {
+        return d;
+    }
+    let before = d.player.hunger();
+    d.player.nutrition = d.player.nutrition.saturating_sub(1);
+    let hunger = d.player.hunger();
+    if hunger != before {
+        d = push_event(d, Event::HungerChanged { hunger });
+    }
+    if hunger != Hunger::Fainting {
+        return d;
+    }
+
+    let mut rng = StdRng::seed_from_u64(d.seed ^ ((d.turn as u64) << 8) ^ 0xfa);
+    if !d.player.statuses.has(StatusKind::Stun) && rng.gen_bool(FAINT_CHANCE) {
+        d.player.statuses.add(Status {
+            kind: StatusKind::Stun,
+            turns: FAINT_TURNS,
+            strength: 1,
+            source: None,
+        });
+        d = push_event(d, Event::PlayerFainted);
+    }
+    if d.player.nutrition == 0 {
+        d.player.hp -= STARVE_DAMAGE;
+        if d.player.hp <= 0 {
+            d.player.alive = false;
+            d = push_event(d, Event::PlayerStarved);
+        }
+    }
+    d
+}
+
+/// Eat the first food in the player's pack, or failing that a corpse or
+/// ration lying under them. Returns whether anything was eaten.
+pub fn eat(mut d: Dungeon) -> (Dungeon, bool) {
+    let carried = d
+        .player
+        .inventory
+        .iter()
+        .flatten()
+        .copied()
+        .find(|item| item.nutrition() > 0);
+    let kind = match carried {
+        Some(kind) => {
+            d.player.remove_item(kind);
+            kind
+        }
+        None => {
+            let (x, y) = (d.player.position_x, d.player.position_y);
+            let under = d.items.iter().position(|i| {
+                i.is_some_and(|i| i.pos_x == x && i.pos_y == y && i.kind.nutrition() > 0)
+            });
+            let Some(slot) = under else {
+                return (d, false);
+            };
+            let kind = d.items[slot].map(|i| i.kind).unwrap_or(ItemKind::Corpse);
+            d.items[slot] = None;
+            kind
+        }
+    };
+    let before = d.player.hunger();
+    d.player.nutrition = (d.player.nutrition + kind.nutrition()).min(MAX_NUTRITION);
+    d = push_event(d, Event::Ate { item: kind });
+    let hunger = d.player.hunger();
+    if hunger != before {
+        d = push_event(d, Event::HungerChanged { hunger });
+    }
+    (d, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ascii_map::load_map;
+    use crate::dungeon::{drop_item, take_events};
+
+    const ROOM: &str = "\
+%%%%%
+%@..%
+%...%
+%%%%%
+";
+
+    /// `ROOM` with the player at the given nutrition, carrying nothing.
+    fn player_at(nutrition: u32) -> Dungeon {
+        let mut d = load_map(ROOM).expect("hunger test map loads");
+        d.player.inventory = [None; 10];
+        d.player.nutrition = nutrition;
+        d
+    }
+
+    #[test]
+    fn hunger_follows_the_thresholds() {
+        for (nutrition, hunger) in [
+            (MAX_NUTRITION, Hunger::Satiated),
+            (1600, Hunger::Satiated),
+            (1599, Hunger::Fed),
+            (300, Hunger::Fed),
+            (299, Hunger::Hungry),
+            (100, Hunger::Hungry),
+            (99, Hunger::Weak),
+            (30, Hunger::Weak),
+            (29, Hunger::Fainting),
+            (0, Hunger::Fainting),
+        ] {
+            assert_eq!(Hunger::of(nutrition), hunger, "at {}", nutrition);
+        }
+    }
+
+    #[test]
+    fn getting_hungrier_is_told_once() {
+        let (d, events) = take_events(tick_hunger(player_at(300)));
+        assert_eq!(d.player.hunger(), Hunger::Hungry);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::HungerChanged {
+                hunger: Hunger::Hungry
+            }
+        )));
+        let (_, events) = take_events(tick_hunger(d));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn fainting_players_pass_out_now_and_then() {
+        let mut d = player_at(29);
+        let mut fainted = 0;
+        for turn in 0..20 {
+            d.turn = turn;
+            d.player.statuses.remove(StatusKind::Stun);
+            let (next, events) = take_events(tick_hunger(d));
+            d = next;
+            if events.iter().any(|e| matches!(e, Event::PlayerFainted)) {
+                assert!(d.player.statuses.has(StatusKind::Stun));
+                fainted += 1;
+            }
+        }
+        assert!(fainted > 0 && fainted < 20);
+        assert!(d.player.alive);
+    }
+
+    #[test]
+    fn an_empty_stomach_starves_to_death() {
+        let mut d = player_at(0);
+        d.player.hp = 2;
+        d = tick_hunger(d);
+        assert!(d.player.alive);
+        let (d, events) = take_events(tick_hunger(d));
+        assert!(!d.player.alive);
+        assert!(events.iter().any(|e| matches!(e, Event::PlayerStarved)));
+    }
+
+    #[test]
+    fn eats_from_the_pack_first() {
+        let mut d = player_at(500);
+        assert!(d.player.add_item(ItemKind::Ration));
+        d = drop_item(d, ItemKind::Corpse, 1, 1).0;
+        let (d, ate) = eat(d);
+        assert!(ate);
+        assert_eq!(d.player.nutrition, 500 + ItemKind::Ration.nutrition());
+        assert!(!d.player.has_item(ItemKind::Ration));
+        assert!(d.items.iter().flatten().any(|i| i.kind == ItemKind::Corpse));
+    }
+
+    #[test]
+    fn eats_a_corpse_underfoot() {
+        let d = drop_item(player_at(500), ItemKind::Corpse, 1, 1).0;
+        let (d, ate) = eat(d);
+        assert!(ate);
+        assert_eq!(d.player.nutrition, 500 + ItemKind::Corpse.nutrition());
+        assert!(d.items.iter().all(Option::is_none));
+        let (_, ate) = eat(d);
+        assert!(!ate);
+    }
+
+    #[test]
+    fn eating_stops_at_full() {
+        let mut d = player_at(MAX_NUTRITION - 1);
+        assert!(d.player.add_item(ItemKind::Ration));
+        let (d, _) = eat(d);
+        assert_eq!(d.player.nutrition, MAX_NUTRITION);
+    }
+}
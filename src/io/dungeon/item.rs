@@ -0,0 +1,348 @@
+use quicksilver::prelude::Color;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ItemKind {
+    Sword,
+    Shield,
+    Darts,
+    Key,
+    Pick,
+    Corpse,
+    Crown,
+    Ration,
+    PotionOfHealing,
+    PotionOfStrength,
+    ScrollOfTeleport,
+    ScrollOfMagicMapping,
+    ScrollOfFear,
+    ScrollOfIdentify,
+    Axe,
+    LeatherArmour,
+    ChainMail,
+    Helmet,
+    RingOfMight,
+    AmuletOfWarding,
+    /// Counted into the player's purse when picked up, see `shop`.
+    Gold,
+    /// The goal of the amulet quest, see `quest`.
+    AncientAmulet,
+}
+
+/// What sort of thing an item is. Potions and scrolls look the same on the
+/// map and hide what they are until identified, see `consumable`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ItemClass {
+    Misc,
+    /// Worn or wielded in an equipment slot, see `equipment`.
+    Gear,
+    Food,
+    Potion,
+    Scroll,
+}
+
+pub struct ItemInfo {
+    pub name: &'static str,
+    pub class: ItemClass,
+    /// Kinds of the same class may share a glyph, in which case a map glyph
+    /// stands for the first of them.
+    pub glyph: char,
+    pub color: Color,
+    /// Hardness removed from rock per turn of digging, 0 if the item can't dig.
+    pub dig_power: u8,
+    /// Nutrition gained by eating the item, 0 if it isn't food.
+    pub nutrition: u32,
+    /// Gold a shop asks for the item; shops buy at half, and not at all at 0.
+    pub price: u32,
+}
+
+// One entry per `ItemKind` variant, in declaration order.
+const ITEM_TABLE: [ItemInfo; 22] = [
+    ItemInfo {
+        name: "Sword",
+        class: ItemClass::Gear,
+        glyph: '|',
+        color: Color::INDIGO,
+        dig_power: 0,
+        nutrition: 0,
+        price: 30,
+    },
+    ItemInfo {
+        name: "Shield",
+        class: ItemClass::Gear,
+        glyph: '[',
+        color: Color::INDIGO,
+        dig_power: 0,
+        nutrition: 0,
+        price: 25,
+    },
+    ItemInfo {
+        name: "Darts",
+        class: ItemClass::Misc,
+        glyph: ')',
+        color: Color::INDIGO,
+        dig_power: 0,
+        nutrition: 0,
+        price: 10,
+    },
+    ItemInfo {
+        name: "Key",
+        class: ItemClass::Misc,
+        glyph: '-',
+        color: Color::ORANGE,
+        dig_power: 0,
+        nutrition: 0,
+        price: 20,
+    },
+    ItemInfo {
+        name: "Pick",
+        class: ItemClass::Misc,
+        glyph: '(',
+        color: Color::ORANGE,
+        dig_power: 50,
+        nutrition: 0,
+        price: 40,
+    },
+    ItemInfo {
+        name: "Corpse",
+        class: ItemClass::Food,
+        glyph: ',',
+        color: Color::MAGENTA,
+        dig_power: 0,
+        nutrition: 300,
+        price: 0,
+    },
+    ItemInfo {
+        name: "Crown",
+        class: ItemClass::Misc,
+        glyph: '*',
+        color: Color::YELLOW,
+        dig_power: 0,
+        nutrition: 0,
+        price: 500,
+    },
+    ItemInfo {
+        name: "Ration",
+        class: ItemClass::Food,
+        glyph: ':',
+        color: Color::ORANGE,
+        dig_power: 0,
+        nutrition: 800,
+        price: 10,
+    },
+    ItemInfo {
+        name: "Potion of healing",
+        class: ItemClass::Potion,
+        glyph: '!',
+        color: Color::RED,
+        dig_power: 0,
+        nutrition: 0,
+        price: 40,
+    },
+    ItemInfo {
+        name: "Potion of strength",
+        class: ItemClass::Potion,
+        glyph: '!',
+        color: Color::RED,
+        dig_power: 0,
+        nutrition: 0,
+        price: 120,
+    },
+    ItemInfo {
+        name: "Scroll of teleport",
+        class: ItemClass::Scroll,
+        glyph: '?',
+        color: Color::BLUE,
+        dig_power: 0,
+        nutrition: 0,
+        price: 50,
+    },
+    ItemInfo {
+        name: "Scroll of magic mapping",
+        class: ItemClass::Scroll,
+        glyph: '?',
+        color: Color::BLUE,
+        dig_power: 0,
+        nutrition: 0,
+        price: 60,
+    },
+    ItemInfo {
+        name: "Scroll of fear",
+        class: ItemClass::Scroll,
+        glyph: '?',
+        color: Color::BLUE,
+        dig_power: 0,
+        nutrition: 0,
+        price: 40,
+    },
+    ItemInfo {
+        name: "Scroll of identify",
+        class: ItemClass::Scroll,
+        glyph: '?',
+        color: Color::BLUE,
+        dig_power: 0,
+        nutrition: 0,
+        price: 20,
+    },
+    ItemInfo {
+        name: "Axe",
+        class: ItemClass::Gear,
+        glyph: '|',
+        color: Color::INDIGO,
+        dig_power: 0,
+        nutrition: 0,
+        price: 80,
+    },
+    ItemInfo {
+        name: "Leather armour",
+        class: ItemClass::Gear,
+        glyph: '[',
+        color: Color::INDIGO,
+        dig_power: 0,
+        nutrition: 0,
+        price: 30,
+    },
+    ItemInfo {
+        name: "Chain mail",
+        class: ItemClass::Gear,
+        glyph: '[',
+        color: Color::INDIGO,
+        dig_power: 0,
+        nutrition: 0,
+        price: 100,
+    },
+    ItemInfo {
+        name: "Helmet",
+        class: ItemClass::Gear,
+        glyph: ']',
+        color: Color::INDIGO,
+        dig_power: 0,
+        nutrition: 0,
+        price: 25,
+    },
+    ItemInfo {
+        name: "Ring of might",
+        class: ItemClass::Gear,
+        glyph: '&',
+        color: Color::YELLOW,
+        dig_power: 0,
+        nutrition: 0,
+        price: 150,
+    },
+    ItemInfo {
+        name: "Amulet of warding",
+        class: ItemClass::Gear,
+        glyph: '"',
+        color: Color::YELLOW,
+        dig_power: 0,
+        nutrition: 0,
+        price: 120,
+    },
+    ItemInfo {
+        name: "Gold",
+        class: ItemClass::Misc,
+        glyph: '$',
+        color: Color::YELLOW,
+        dig_power: 0,
+        nutrition: 0,
+        price: 0,
+    },
+    ItemInfo {
+        name: "Ancient amulet",
+        class: ItemClass::Misc,
+        glyph: '{',
+        color: Color::ORANGE,
+        dig_power: 0,
+        nutrition: 0,
+        price: 0,
+    },
+];
+
+impl ItemKind {
+    pub const ALL: [ItemKind; 22] = [
+        ItemKind::Sword,
+        ItemKind::Shield,
+        ItemKind::Darts,
+        ItemKind::Key,
+        ItemKind::Pick,
+        ItemKind::Corpse,
+        ItemKind::Crown,
+        ItemKind::Ration,
+        ItemKind::PotionOfHealing,
+        ItemKind::PotionOfStrength,
+        ItemKind::ScrollOfTeleport,
+        ItemKind::ScrollOfMagicMapping,
+        ItemKind::ScrollOfFear,
+        ItemKind::ScrollOfIdentify,
+        ItemKind::Axe,
+        ItemKind::LeatherArmour,
+        ItemKind::ChainMail,
+        ItemKind::Helmet,
+        ItemKind::RingOfMight,
+        ItemKind::AmuletOfWarding,
+        ItemKind::Gold,
+        ItemKind::AncientAmulet,
+    ];
+
+    pub fn info(self) -> &'static ItemInfo {
+        &ITEM_TABLE[self as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        self.info().name
+    }
+
+    pub fn class(self) -> ItemClass {
+        self.info().class
+    }
+
+    pub fn glyph(self) -> char {
+        self.info().glyph
+    }
+
+    pub fn color(self) -> Color {
+        self.info().color
+    }
+
+    pub fn dig_power(self) -> u8 {
+        self.info().dig_power
+    }
+
+    pub fn nutrition(self) -> u32 {
+        self.info().nutrition
+    }
+
+    pub fn price(self) -> u32 {
+        self.info().price
+    }
+
+    pub fn from_glyph(glyph: char) -> Option<ItemKind> {
+        ItemKind::ALL.iter().copied().find(|i| i.glyph() == glyph)
+    }
+}
+
+/// An item lying on the dungeon floor.
+#[derive(Copy, Clone)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub pos_x: usize,
+    pub pos_y: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_glyphs_stay_within_a_class() {
+        for kind in ItemKind::ALL {
+            let first = ItemKind::from_glyph(kind.glyph()).expect("glyph is known");
+            assert_eq!(
+                first.info().class,
+                kind.info().class,
+                "{} shares its glyph with {}",
+                kind.name(),
+                first.name()
+            );
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::terrain::Terrain;
+use crate::dungeon::{drop_item, Dungeon};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::OnceLock;
+
+/// Mixed into the seed for drops, so they don't follow other per-turn rolls.
+const LOOT_SALT: u64 = 0x100d;
+
+/// How rare a loot table entry is, from most to least common.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    Legendary,
+}
+
+pub struct RarityInfo {
+    pub name: &'static str,
+    /// Weight of an entry of this rarity on the first level.
+    pub weight: u32,
+    /// Added to the weight for each level deeper.
+    pub depth_bonus: u32,
+}
+
+// One entry per `Rarity` variant, in declaration order.
+const RARITY_TABLE: [RarityInfo; 4] = [
+    RarityInfo {
+        name: "common",
+        weight: 100,
+        depth_bonus: 0,
+    },
+    RarityInfo {
+        name: "uncommon",
+        weight: 40,
+        depth_bonus: 10,
+    },
+    RarityInfo {
+        name: "rare",
+        weight: 12,
+        depth_bonus: 6,
+    },
+    RarityInfo {
+        name: "legendary",
+        weight: 3,
+        depth_bonus: 3,
+    },
+];
+
+impl Rarity {
+    pub const ALL: [Rarity; 4] = [
+        Rarity::Common,
+        Rarity::Uncommon,
+        Rarity::Rare,
+        Rarity::Legendary,
+    ];
+
+    pub fn info(self) -> &'static RarityInfo {
+        &RARITY_TABLE[self as usize]
+    }
+
+    pub fn from_name(name: &str) -> Option<Rarity> {
+        Rarity::ALL
+            .iter()
+            .copied()
+            .find(|r| r.info().name.eq_ignore_ascii_case(name))
+    }
+}
+
+pub struct LootEntry {
+    pub item: ItemKind,
+    pub rarity: Rarity,
+    pub min_depth: u32,
+    /// `None` for no limit.
+    pub max_depth: Option<u32>,
+}
+
+impl LootEntry {
+    /// How likely the entry is to be picked at a depth, 0 outside its depths.
+    pub fn weight(&self, depth: u32) -> u32 {
+        if depth < self.min_depth || self.max_depth.is_some_and(|max| depth > max) {
+            return 0;
+        }
+        let info = self.rarity.info();
+        info.weight + info.depth_bonus * (depth - 1)
+    }
+}
+
+pub struct LootTable {
+    pub name: &'static str,
+    /// Percent chance that a roll gives an item at all.
+    pub chance: u32,
+    pub rolls: u32,
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    /// One roll of the table, `None` when it gives nothing.
+    pub fn roll_once(&self, depth: u32, rng: &mut StdRng) -> Option<ItemKind> {
+        if rng.gen_range(0..100) >= self.chance {
+            return None;
+        }
+        let total: u32 = self.entries.iter().map(|e| e.weight(depth)).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0..total);
+        for entry in self.entries.iter() {
+            let weight = entry.weight(depth);
+            if pick < weight {
+                return Some(entry.item);
+            }
+            pick -= weight;
+        }
+        None
+    }
+
+    /// Every item the table's rolls give at a depth.
+    pub fn roll(&self, depth: u32, rng: &mut StdRng) -> Vec<ItemKind> {
+        (0..self.rolls)
+            .filter_map(|_| self.roll_once(depth, rng))
+            .collect()
+    }
+}
+
+/// The loot tables shipped in `static/loot.txt`, parsed the first time
+/// they are needed, which is when the first level is generated.
+pub fn tables() -> &'static [LootTable] {
+    static TABLES: OnceLock<Vec<LootTable>> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        parse_tables(include_str!("../../../static/loot.txt"))
+            .expect("Could not parse the loot tables.")
+    })
+}
+
+pub fn table<'a>(tables: &'a [LootTable], name: &str) -> Option<&'a LootTable> {
+    tables.iter().find(|t| t.name.eq_ignore_ascii_case(name))
+}
+
+/// Tables are blocks of `key: value` lines separated by blank lines, each
+/// starting with `table:`, then `chance:` and `rolls:`, then one line per
+/// item. Lines starting with `;` are comments.
+pub fn parse_tables(text: &'static str) -> Result<Vec<LootTable>, String> {
+    let mut tables = Vec::new();
+    let mut block: Vec<(&'static str, &'static str)> = Vec::new();
+
+    for line in text.lines().chain(std::iter::once("")) {
+        if line.starts_with(';') {
+            continue;
+        }
+        if line.trim().is_empty() {
+            if !block.is_empty() {
+                tables.push(parse_table(&block)?);
+                block.clear();
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            return Err(format!("expected 'key: value', got '{}'", line));
+        };
+        block.push((key.trim(), value.trim()));
+    }
+    Ok(tables)
+}
+
+fn parse_table(block: &[(&'static str, &'static str)]) -> Result<LootTable, String> {
+    let (key, name) = block[0];
+    if key != "table" {
+        return Err(format!("expected a table, got '{}'", key));
+    }
+    let mut table = LootTable {
+        name,
+        chance: 100,
+        rolls: 1,
+        entries: Vec::new(),
+    };
+    let number = |key: &str, value: &str| {
+        value
+            .parse::<u32>()
+            .map_err(|_| format!("{}'s {} is not a number", name, key))
+    };
+    for &(key, value) in block[1..].iter() {
+        match key {
+            "chance" => table.chance = number(key, value)?,
+            "rolls" => table.rolls = number(key, value)?,
+            _ => {
+                let entry = parse_entry(name, key, value)?;
+                if table.entries.iter().any(|e| e.item == entry.item) {
+                    return Err(format!("{} lists {} twice", name, key));
+                }
+                table.entries.push(entry);
+            }
+        }
+    }
+    if table.entries.is_empty() {
+        return Err(format!("{} has no items", name));
+    }
+    Ok(table)
+}
+
+/// An item line's value is its rarity, then optionally its depths.
+fn parse_entry(table: &str, item: &str, value: &str) -> Result<LootEntry, String> {
+    let item = ItemKind::ALL
+        .iter()
+        .copied()
+        .find(|kind| kind.name().eq_ignore_ascii_case(item))
+        .ok_or_else(|| format!("{} has unknown item '{}'", table, item))?;
+    let (rarity, depths) = match value.split_once(',') {
+        Some((rarity, depths)) => (rarity.trim(), Some(depths.trim())),
+        None => (value, None),
+    };
+    let rarity = Rarity::from_name(rarity)
+        .ok_or_else(|| format!("{} has unknown rarity '{}'", table, rarity))?;
+    let depth = |text: &str| {
+        text.trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|&depth| depth > 0)
+            .ok_or_else(|| format!("{} has bad depths '{}'", table, value))
+    };
+    let (min_depth, max_depth) = match depths {
+        None => (1, None),
+        Some(depths) => match depths.split_once('-') {
+            Some((min, max)) => (depth(min)?, Some(depth(max)?)),
+            None => (depth(depths)?, None),
+        },
+    };
+    Ok(LootEntry {
+        item,
+        rarity,
+        min_depth,
+        max_depth,
+    })
+}
+
+/// Drop whatever a dying monster's table gives where it lies.
+pub fn drop_loot(mut d: Dungeon, monster: usize) -> Dungeon {
+    let tables = tables();
+    let kind = d.monsters[monster].kind;
+    let Some(table) = table(tables, kind.info().name).or_else(|| table(tables, "monster")) else {
+        return d;
+    };
+    let salt = LOOT_SALT ^ monster as u64;
+    let mut rng = StdRng::seed_from_u64(d.seed ^ ((d.turn as u64) << 8) ^ salt);
+    let (x, y) = (d.monsters[monster].pos_x, d.monsters[monster].pos_y);
+    for item in table.roll(d.depth, &mut rng) {
+        d = drop_item(d, item, x, y).0;
+    }
+    d
+}
+
+/// Scatter a table's items over free floor in a room, given as
+/// (x, y, length, width) like the generator's room lists.
+pub fn fill_room(
+    mut d: Dungeon,
+    name: &str,
+    room: (usize, usize, usize, usize),
+    rng: &mut StdRng,
+) -> Dungeon {
+    let tables = tables();
+    let Some(table) = table(tables, name) else {
+        return d;
+    };
+    let (x, y, length, width) = room;
+    let mut cells: Vec<(usize, usize)> = (x..x + width)
+        .flat_map(|cx| (y..y + length).map(move |cy| (cx, cy)))
+        .collect();
+    for item in table.roll(d.depth, rng) {
+        cells.retain(|&(cx, cy)| {
+            let taken = d
+                .items
+                .iter()
+                .flatten()
+                .any(|i| (i.pos_x, i.pos_y) == (cx, cy));
+            let player = (d.player.position_x, d.player.position_y) == (cx, cy);
+            let monster = d.monsters.iter().any(|m| (m.pos_x, m.pos_y) == (cx, cy));
+            d.terrain_map[cx][cy] == Terrain::Floor && !taken && !player && !monster
+        });
+        if cells.is_empty() {
+            break;
+        }
+        let (cx, cy) = cells[rng.gen_range(0..cells.len())];
+        d = drop_item(d, item, cx, cy).0;
+    }
+    d
+}
+
+/// Depths the loot check rolls the tables at.
+const CHECK_DEPTHS: [u32; 3] = [1, 3, 5];
+
+/// Roll every table many times at a few depths and print how often each
+/// item came up against how often its weight says it should. Fails when an
+/// item turns up outside its depths, or strays further from its share than
+/// chance allows. Returns whether all tables passed.
+pub fn check_loot(rolls: usize) -> bool {
+    let mut failures = 0;
+    for table in tables().iter() {
+        for depth in CHECK_DEPTHS {
+            let mut rng = StdRng::seed_from_u64(depth as u64);
+            let mut counts = vec![0usize; table.entries.len()];
+            let mut nothing = 0;
+            for _ in 0..rolls {
+                let item = table.roll_once(depth, &mut rng);
+                match table.entries.iter().position(|e| Some(e.item) == item) {
+                    Some(i) => counts[i] += 1,
+                    None => nothing += 1,
+                }
+            }
+
+            println!("{} at depth {}, {} rolls:", table.name, depth, rolls);
+            let total: u32 = table.entries.iter().map(|e| e.weight(depth)).sum();
+            let chance = table.chance as f64 / 100.0;
+            let mut shares: Vec<(String, f64, usize)> = table
+                .entries
+                .iter()
+                .zip(counts.iter())
+                .map(|(entry, &count)| {
+                    let share = match total {
+                        0 => 0.0,
+                        _ => chance * entry.weight(depth) as f64 / total as f64,
+                    };
+                    let name = format!("{:<10} {}", entry.rarity.info().name, entry.item.name());
+                    (name, share, count)
+                })
+                .collect();
+            let expected_nothing = if total == 0 { 1.0 } else { 1.0 - chance };
+            shares.push((format!("{:<10} nothing", ""), expected_nothing, nothing));
+
+            for (name, share, count) in shares {
+                let seen = count as f64 / rolls as f64;
+                // Four standard deviations, and never a hit for a zero share
+                let spread = 4.0 * (share * (1.0 - share) / rolls as f64).sqrt();
+                let passed = (seen - share).abs() <= spread && (share > 0.0 || count == 0);
+                if !passed {
+                    failures += 1;
+                }
+                println!(
+                    "  {:<36} {:>6.2}% (expected {:>6.2}%){}",
+                    name,
+                    seen * 100.0,
+                    share * 100.0,
+                    if passed { "" } else { " FAILED" }
+                );
+            }
+        }
+    }
+    println!(
+        "{} loot tables checked, {} shares off",
+        tables().len(),
+        failures
+    );
+    failures == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_tables_parse() {
+        let tables = parse_tables(include_str!("../../../static/loot.txt")).expect("tables parse");
+        for name in ["monster", "treasure", "floor", "shop"] {
+            assert!(table(&tables, name).is_some(), "no {} table", name);
+        }
+    }
+
+    #[test]
+    fn bad_tables_are_refused() {
+        assert!(parse_tables("table: t\nSword: mythic\n").is_err());
+        assert!(parse_tables("table: t\nSpoon: common\n").is_err());
+        assert!(parse_tables("table: t\nSword: rare, 0\n").is_err());
+        assert!(parse_tables("table: t\nchance: 50\n").is_err());
+    }
+
+    #[test]
+    fn entries_keep_to_their_depths() {
+        let tables = parse_tables("table: t\nSword: common, 2-3\nAxe: rare, 4\n").expect("parses");
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..200 {
+            assert_eq!(tables[0].roll_once(1, &mut rng), None);
+            assert_eq!(tables[0].roll_once(2, &mut rng), Some(ItemKind::Sword));
+            assert_eq!(tables[0].roll_once(5, &mut rng), Some(ItemKind::Axe));
+        }
+    }
+}
@@ -1,7 +1,229 @@
+use crate::dungeon::ability::{Ability, AbilityInfo, MAX_ABILITIES};
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::status::Statuses;
 use quicksilver::prelude::Color;
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MonsterKind {
+    Goblin,
+    Rat,
+    RockWorm,
+    GoblinChief,
+    OgreKing,
+    Spitter,
+    Shopkeeper,
+}
+
+/// Position of a roster slot that hasn't been put on the map yet.
+pub const UNPLACED: usize = 100;
+
+/// Hardness a tunnelling monster removes from rock each turn.
+pub const TUNNEL_POWER: u8 = 64;
+
+/// Base stats shared by every monster of one kind.
+pub struct MonsterInfo {
+    pub name: &'static str,
+    pub character: char,
+    pub hp: i32,
+    pub attack: i32,
+    pub defence: i32,
+    pub color: Color,
+    pub opens_doors: bool,
+    pub tunnels: bool,
+    /// Monsters with higher initiative win contested cells and move first.
+    pub initiative: i32,
+    pub sight_radius: usize,
+    /// Extra distance at which the monster hears the player's noise.
+    pub hearing: u16,
+    /// Percentage of its hp below which the monster flees, 0 to never flee.
+    pub morale: i32,
+    /// Kind and number of followers spawned with the monster as their leader.
+    pub escort: Option<(MonsterKind, usize)>,
+    /// Awarded to the player for the kill.
+    pub xp: u32,
+    /// At most `MAX_ABILITIES`.
+    pub abilities: &'static [AbilityInfo],
+    /// Whether the monster starts out leaving the player alone.
+    pub peaceful: bool,
+}
+
+// One entry per `MonsterKind` variant, in declaration order.
+const MONSTER_TABLE: [MonsterInfo; 7] = [
+    // Goblin
+    MonsterInfo {
+        name: "goblin",
+        character: 'g',
+        hp: 10,
+        attack: 1,
+        defence: 1,
+        color: Color::BLUE,
+        opens_doors: true,
+        tunnels: false,
+        initiative: 10,
+        sight_radius: 8,
+        hearing: 2,
+        morale: 30,
+        escort: None,
+        xp: 5,
+        abilities: &[AbilityInfo {
+            ability: Ability::Steal,
+            cooldown: 20,
+        }],
+        peaceful: false,
+    },
+    // Rat
+    MonsterInfo {
+        name: "rat",
+        character: 'r',
+        hp: 4,
+        attack: 1,
+        defence: 0,
+        color: Color::ORANGE,
+        opens_doors: false,
+        tunnels: false,
+        initiative: 14,
+        sight_radius: 5,
+        hearing: 4,
+        morale: 50,
+        escort: None,
+        xp: 2,
+        abilities: &[AbilityInfo {
+            ability: Ability::Poison { turns: 5 },
+            cooldown: 5,
+        }],
+        peaceful: false,
+    },
+    // RockWorm
+    MonsterInfo {
+        name: "rock worm",
+        character: 'w',
+        hp: 15,
+        attack: 2,
+        defence: 2,
+        color: Color::PURPLE,
+        opens_doors: false,
+        tunnels: true,
+        initiative: 4,
+        sight_radius: 3,
+        hearing: 6,
+        morale: 0,
+        escort: None,
+        xp: 8,
+        abilities: &[AbilityInfo {
+            ability: Ability::Regenerate { amount: 1 },
+            cooldown: 1,
+        }],
+        peaceful: false,
+    },
+    // GoblinChief
+    MonsterInfo {
+        name: "goblin chief",
+        character: 'G',
+        hp: 18,
+        attack: 3,
+        defence: 2,
+        color: Color::RED,
+        opens_doors: true,
+        tunnels: false,
+        initiative: 10,
+        sight_radius: 8,
+        hearing: 2,
+        morale: 20,
+        escort: Some((MonsterKind::Goblin, 3)),
+        xp: 20,
+        abilities: &[AbilityInfo {
+            ability: Ability::Summon {
+                kind: MonsterKind::Goblin,
+            },
+            cooldown: 10,
+        }],
+        peaceful: false,
+    },
+    // OgreKing
+    MonsterInfo {
+        name: "ogre king",
+        character: 'O',
+        hp: 60,
+        attack: 40,
+        defence: 3,
+        color: Color::MAGENTA,
+        opens_doors: true,
+        tunnels: false,
+        initiative: 6,
+        sight_radius: 7,
+        hearing: 2,
+        morale: 0,
+        escort: None,
+        xp: 100,
+        abilities: &[],
+        peaceful: false,
+    },
+    // Spitter
+    MonsterInfo {
+        name: "cave spitter",
+        character: 's',
+        hp: 8,
+        attack: 30,
+        defence: 0,
+        color: Color::GREEN,
+        opens_doors: false,
+        tunnels: false,
+        initiative: 8,
+        sight_radius: 8,
+        hearing: 2,
+        morale: 40,
+        escort: None,
+        xp: 6,
+        abilities: &[AbilityInfo {
+            ability: Ability::Spit { range: 5 },
+            cooldown: 2,
+        }],
+        peaceful: false,
+    },
+    // Shopkeeper: minds the shop, and is deadly once crossed
+    MonsterInfo {
+        name: "shopkeeper",
+        character: 'S',
+        hp: 150,
+        attack: 80,
+        defence: 8,
+        color: Color::INDIGO,
+        opens_doors: true,
+        tunnels: false,
+        initiative: 12,
+        sight_radius: 10,
+        hearing: 4,
+        morale: 0,
+        escort: None,
+        xp: 60,
+        abilities: &[],
+        peaceful: true,
+    },
+];
+
+impl MonsterKind {
+    pub const ALL: [MonsterKind; 7] = [
+        MonsterKind::Goblin,
+        MonsterKind::Rat,
+        MonsterKind::RockWorm,
+        MonsterKind::GoblinChief,
+        MonsterKind::OgreKing,
+        MonsterKind::Spitter,
+        MonsterKind::Shopkeeper,
+    ];
+
+    pub fn info(self) -> &'static MonsterInfo {
+        &MONSTER_TABLE[self as usize]
+    }
+
+    pub fn from_glyph(glyph: char) -> Option<MonsterKind> {
+        MonsterKind::ALL.iter().copied().find(|m| m.info().character == glyph)
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Monster {
+    pub kind: MonsterKind,
     pub character: char,
     pub pos_x: usize,
     pub pos_y: usize,
@@ -11,11 +233,73 @@ pub struct Monster {
     pub defence: i32,
     pub alive: bool,
     pub color: Color,
+    pub awake: bool,
+    /// Where the monster last saw or heard the player.
+    pub last_seen: Option<(usize, usize)>,
+    pub fleeing: bool,
+    /// Roster slot of the leader of the monster's pack, its own for a leader.
+    pub pack: Option<usize>,
+    /// Current phase of a boss fight, `None` until the boss notices the player.
+    pub phase: Option<usize>,
+    /// Turns until a boss's special attack is ready again.
+    pub cooldown: u32,
+    /// Turns until each of the kind's abilities is ready again.
+    pub ability_cooldowns: [u32; MAX_ABILITIES],
+    /// An item stolen from the player, dropped when the monster dies.
+    pub carrying: Option<ItemKind>,
+    pub statuses: Statuses,
+    /// Leaves the player alone, neither moving nor attacking, until
+    /// provoked, see `shop::provoke`.
+    pub peaceful: bool,
 }
 
 impl Monster {
+    pub fn new(kind: MonsterKind) -> Monster {
+        let info = kind.info();
+        Monster {
+            kind,
+            character: info.character,
+            pos_x: UNPLACED,
+            pos_y: UNPLACED,
+            hp: info.hp,
+            max_hp: info.hp,
+            attack: info.attack,
+            defence: info.defence,
+            alive: true,
+            color: info.color,
+            awake: true,
+            last_seen: None,
+            fleeing: false,
+            pack: None,
+            phase: None,
+            cooldown: 0,
+            ability_cooldowns: [0; MAX_ABILITIES],
+            carrying: None,
+            statuses: Statuses::default(),
+            peaceful: info.peaceful,
+        }
+    }
+
+    /// Take a roster slot out of play, parked in the map's corner.
+    pub fn park(&mut self) {
+        self.alive = false;
+        self.pos_x = 0;
+        self.pos_y = 0;
+    }
+
     pub fn process_combat(self, player_attack: i32) -> i32 {
-        let hp_after = self.hp - (player_attack - self.defence);
-        hp_after
+        self.hp - (player_attack - self.defence)
+    }
+
+    pub fn opens_doors(self) -> bool {
+        self.kind.info().opens_doors
+    }
+
+    pub fn tunnels(self) -> bool {
+        self.kind.info().tunnels
+    }
+
+    pub fn initiative(self) -> i32 {
+        self.kind.info().initiative + self.statuses.initiative()
     }
 }
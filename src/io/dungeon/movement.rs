@@ -0,0 +1,275 @@
+use crate::dungeon::monster::TUNNEL_POWER;
+use crate::dungeon::{dig, set_terrain, Dungeon};
+
+/// Move every monster at once.
+///
+/// Each monster first picks the cell it wants. Monsters opening a door or
+/// tunnelling do that and stay put. The rest are resolved in passes, highest
+/// initiative first: a monster moves once its cell is free, two monsters
+/// that want each other's cells swap, and a monster whose cell was taken or
+/// whose way is blocked by a monster that isn't moving stays where it is.
+/// Busy monsters, which spent their turn on something else, stay put too.
+pub fn resolve_monster_moves(mut d: Dungeon, busy: &[usize]) -> Dungeon {
+    let mut order: Vec<usize> = (0..d.monsters.len())
+        .filter(|&i| d.monsters[i].alive)
+        .collect();
+    order.sort_by_key(|&i| (-d.monsters[i].initiative(), i));
+
+    let mut intent = vec![(0, 0); d.monsters.len()];
+    for &i in order.iter() {
+        if busy.contains(&i) {
+            intent[i] = (d.monsters[i].pos_x, d.monsters[i].pos_y);
+            continue;
+        }
+        let (x, y) = d.determine_monster_move(i);
+        intent[i] = (x, y);
+
+        // Doors and rock take the turn instead of a step
+        let terrain = d.terrain_map[x][y];
+        if let Some(open) = terrain.opens_to() {
+            d = set_terrain(d, x, y, open);
+            intent[i] = (d.monsters[i].pos_x, d.monsters[i].pos_y);
+        } else if terrain.diggable() {
+            d = dig(d, x, y, TUNNEL_POWER).0;
+            intent[i] = (d.monsters[i].pos_x, d.monsters[i].pos_y);
+        }
+    }
+
+    let mut resolved = vec![false; d.monsters.len()];
+    let mut claimed = [[false; 80]; 30];
+    let occupant = |d: &Dungeon, x: usize, y: usize| {
+        order
+            .iter()
+            .copied()
+            .find(|&j| d.monsters[j].pos_x == x && d.monsters[j].pos_y == y)
+    };
+
+    let mut progress = true;
+    while progress {
+        progress = false;
+        for &i in order.iter() {
+            if resolved[i] {
+                continue;
+            }
+            let here = (d.monsters[i].pos_x, d.monsters[i].pos_y);
+            let (tx, ty) = intent[i];
+
+            if (tx, ty) == here || claimed[tx][ty] {
+                // Staying, or someone faster already took the cell
+                resolved[i] = true;
+                claimed[here.0][here.1] = true;
+                progress = true;
+                continue;
+            }
+            match occupant(&d, tx, ty) {
+                None => {
+                    d.monsters[i].pos_x = tx;
+                    d.monsters[i].pos_y = ty;
+                    resolved[i] = true;
+                    claimed[tx][ty] = true;
+                    progress = true;
+                }
+                Some(j) if resolved[j] => {
+                    // The monster in the way is staying
+                    resolved[i] = true;
+                    claimed[here.0][here.1] = true;
+                    progress = true;
+                }
+                Some(j) if intent[j] == here => {
+                    d.monsters[i].pos_x = tx;
+                    d.monsters[i].pos_y = ty;
+                    d.monsters[j].pos_x = here.0;
+                    d.monsters[j].pos_y = here.1;
+                    resolved[i] = true;
+                    resolved[j] = true;
+                    claimed[tx][ty] = true;
+                    claimed[here.0][here.1] = true;
+                    progress = true;
+                }
+                // Wait for the monster in the way to be resolved
+                Some(_) => {}
+            }
+        }
+    }
+    // Anything left is stuck in a loop of monsters waiting on each other,
+    // and simply stays where it is
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ability::MAX_ABILITIES;
+    use crate::dungeon::ascii_map::{export_map, load_map};
+    use crate::dungeon::monster::Monster;
+    use crate::dungeon::{monster_map, take_turn};
+
+    /// Load a crowded map, let every monster know where the player is and
+    /// run a few monster turns. Abilities have their own tests, so they are
+    /// kept on cooldown here.
+    fn run(before: &str, turns: usize, setup: impl Fn(&mut Monster)) -> Dungeon {
+        let mut d = load_map(before).expect("movement test map loads");
+        let player = (d.player.position_x, d.player.position_y);
+        for monster in d.monsters.iter_mut().filter(|m| m.alive) {
+            monster.last_seen = Some(player);
+            monster.ability_cooldowns = [u32::MAX; MAX_ABILITIES];
+            setup(monster);
+        }
+        for _ in 0..turns {
+            d = monster_map(d);
+            d = take_turn(d);
+        }
+        d
+    }
+
+    /// Monsters in a map file stand on floor, so that is what they leave
+    /// behind.
+    fn assert_map(d: &Dungeon, after: &str) {
+        let teleported = d
+            .monsters
+            .iter()
+            .any(|m| m.alive && (m.pos_x == 0 || m.pos_y == 0));
+        assert!(!teleported, "a monster jumped to the corner");
+        assert_eq!(export_map(d), after);
+    }
+
+    #[test]
+    fn corridor_queue_follows_the_leader() {
+        let d = run(
+            "%%%%%%%%%%%%%%%%
+%@.###ggrg###..%
+%%%%%%%%%%%%%%%%
+",
+            1,
+            |_| {},
+        );
+        assert_map(
+            &d,
+            "%%%%%%%%%%%%%%%%
+%@.##ggrg.###..%
+%%%%%%%%%%%%%%%%
+",
+        );
+    }
+
+    #[test]
+    fn queue_behind_a_rat_at_a_door_stays_put() {
+        let d = run(
+            "%%%%%%%%%%
+%@..+rggg%
+%%%%%%%%%%
+",
+            1,
+            |_| {},
+        );
+        assert_map(
+            &d,
+            "%%%%%%%%%%
+%@..+rggg%
+%%%%%%%%%%
+",
+        );
+    }
+
+    #[test]
+    fn higher_initiative_wins_a_contested_cell() {
+        let d = run(
+            "%%%%%%%
+%@....%
+%%%.%%%
+%%g%r%%
+%%%%%%%
+",
+            1,
+            |_| {},
+        );
+        assert_map(
+            &d,
+            "%%%%%%%
+%@....%
+%%%r%%%
+%%g%.%%
+%%%%%%%
+",
+        );
+    }
+
+    #[test]
+    fn wounded_goblin_flees_past_the_player() {
+        let d = run(
+            "%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%@..g..%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%.%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%.%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%......................................%
+%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+",
+            1,
+            |m| m.hp = 1,
+        );
+        assert_map(
+            &d,
+            "%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%@.....%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%g%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%.%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%......................................%
+%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+",
+        );
+    }
+
+    #[test]
+    fn wounded_rat_flees_away_from_a_door_it_cannot_open() {
+        let d = run(
+            "%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%@..r..%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%+%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%.%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%......................................%
+%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+",
+            2,
+            |m| m.hp = 1,
+        );
+        assert_map(
+            &d,
+            "%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%@....r%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%+%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%.%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+%%%......................................%
+%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+",
+        );
+    }
+
+    #[test]
+    fn pack_surrounds_the_player() {
+        let d = run(
+            "%%%%%%%%%%%%
+%..........%
+%..........%
+%....@.....%
+%..........%
+%.......G..%
+%......ggg.%
+%%%%%%%%%%%%
+",
+            6,
+            |m| m.pack = Some(0),
+        );
+        assert_map(
+            &d,
+            "%%%%%%%%%%%%
+%..........%
+%...g......%
+%....@G....%
+%...g.g....%
+%..........%
+%..........%
+%%%%%%%%%%%%
+",
+        );
+    }
+}
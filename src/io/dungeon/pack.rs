@@ -0,0 +1,121 @@
+use crate::dungeon::{neighbourhood, Dungeon};
+use std::cmp::Reverse;
+
+/// How far (in steps either way) a follower may stray from its leader.
+const FORMATION_RADIUS: usize = 2;
+
+/// Followers closer than this (in distance map steps) to the player stop
+/// chasing and spread out around them.
+const SURROUND_RANGE: u16 = 6;
+
+/// Whether monster `i` follows a leader that is still alive.
+fn follower_of(d: &Dungeon, i: usize) -> Option<usize> {
+    match d.monsters[i].pack {
+        Some(leader) if leader != i && d.monsters[leader].alive => Some(leader),
+        _ => None,
+    }
+}
+
+/// When one member of a pack knows where the player is, so do the others,
+/// and they all wake up. The freshest sighting wins.
+pub fn alert_packs(mut d: Dungeon) -> Dungeon {
+    let player = (d.player.position_x, d.player.position_y);
+    for leader in 0..d.monsters.len() {
+        let members: Vec<usize> = (0..d.monsters.len())
+            .filter(|&i| d.monsters[i].alive && d.monsters[i].pack == Some(leader))
+            .collect();
+        let sightings: Vec<(usize, usize)> = members
+            .iter()
+            .filter(|&&i| d.monsters[i].awake)
+            .filter_map(|&i| d.monsters[i].last_seen)
+            .collect();
+        let Some(&first) = sightings.first() else {
+            continue;
+        };
+        let goal = if sightings.contains(&player) {
+            player
+        } else {
+            first
+        };
+        for &i in members.iter() {
+            d.monsters[i].awake = true;
+            d.monsters[i].last_seen = Some(goal);
+        }
+    }
+    d
+}
+
+fn chebyshev(a: (usize, usize), b: (usize, usize)) -> usize {
+    a.0.abs_diff(b.0).max(a.1.abs_diff(b.1))
+}
+
+/// Share out the cells around the player among the followers of a leader
+/// that are closing in, in roster order. Each takes the free cell furthest
+/// from the rest of the pack, the nearest one to itself if there's a tie.
+fn surround_cells(d: &Dungeon, leader: usize) -> Vec<(usize, (usize, usize))> {
+    let player = (d.player.position_x, d.player.position_y);
+    let mut taken = vec![(d.monsters[leader].pos_x, d.monsters[leader].pos_y)];
+    let mut cells = Vec::new();
+
+    for j in 0..d.monsters.len() {
+        let monster = d.monsters[j];
+        let here = (monster.pos_x, monster.pos_y);
+        let closing_in = follower_of(d, j) == Some(leader)
+            && monster.last_seen == Some(player)
+            && d.distance_map[here.0][here.1] <= SURROUND_RANGE;
+        if !closing_in {
+            continue;
+        }
+        let free = |&(x, y): &(usize, usize)| {
+            (x, y) != player
+                && d.terrain_map[x][y].passable()
+                && !taken.contains(&(x, y))
+                && !d
+                    .monsters
+                    .iter()
+                    .enumerate()
+                    .any(|(k, m)| k != j && m.alive && (m.pos_x, m.pos_y) == (x, y))
+        };
+        let best = neighbourhood(player.0, player.1)
+            .filter(free)
+            .max_by_key(|&cell| {
+                let spread = taken.iter().map(|&t| chebyshev(t, cell)).min();
+                (spread, Reverse(chebyshev(here, cell)))
+            });
+        if let Some(cell) = best {
+            taken.push(cell);
+            cells.push((j, cell));
+        }
+    }
+    cells
+}
+
+/// The cell a follower is heading for instead of the player, if any.
+///
+/// Followers that don't know where the player is keep close to their leader.
+/// Once the pack is closing in, each follower picks its own side of the
+/// player so they surround them rather than queueing up behind each other.
+/// Leaders and monsters on their own go straight for the player.
+pub fn pack_goal(d: &Dungeon, i: usize) -> Option<(usize, usize)> {
+    let leader = follower_of(d, i)?;
+    let monster = d.monsters[i];
+    let player = (d.player.position_x, d.player.position_y);
+
+    match monster.last_seen {
+        None => {
+            let (lx, ly) = (d.monsters[leader].pos_x, d.monsters[leader].pos_y);
+            let near = monster.pos_x.abs_diff(lx) <= FORMATION_RADIUS
+                && monster.pos_y.abs_diff(ly) <= FORMATION_RADIUS;
+            if near {
+                Some((monster.pos_x, monster.pos_y))
+            } else {
+                Some((lx, ly))
+            }
+        }
+        Some(seen) if seen == player => surround_cells(d, leader)
+            .into_iter()
+            .find(|&(j, _)| j == i)
+            .map(|(_, cell)| cell),
+        Some(_) => None,
+    }
+}
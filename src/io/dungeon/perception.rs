@@ -0,0 +1,155 @@
+use crate::dungeon::character::Talent;
+use crate::dungeon::Dungeon;
+
+/// How far (in distance map steps) the player's actions carry.
+pub const NOISE_WALK: u16 = 3;
+pub const NOISE_DOOR: u16 = 5;
+pub const NOISE_SPELL: u16 = 6;
+pub const NOISE_DIG: u16 = 8;
+pub const NOISE_ATTACK: u16 = 12;
+
+/// Make some noise this turn. Only the loudest action counts, and stealthy
+/// players make half as much.
+pub fn make_noise(mut d: Dungeon, noise: u16) -> Dungeon {
+    let noise = if d.player.has_talent(Talent::Stealth) {
+        noise / 2
+    } else {
+        noise
+    };
+    d.noise = d.noise.max(noise);
+    d
+}
+
+/// Whether a monster standing at (x, y) can see the player: within its sight
+/// radius and with nothing opaque in between.
+fn sees_player(d: &Dungeon, x: usize, y: usize, radius: usize) -> bool {
+    let player = (d.player.position_x, d.player.position_y);
+    let dx = x.abs_diff(player.0);
+    let dy = y.abs_diff(player.1);
+    dx * dx + dy * dy <= radius * radius && d.line_of_sight((x, y), player)
+}
+
+/// Let every monster look and listen before it moves.
+///
+/// Sleeping monsters don't look, and only hear the player at half the usual
+/// range, so it is possible to sneak past them. Awake monsters that see or
+/// hear the player remember where, and head there until they arrive and find
+/// nobody.
+pub fn update_perception(mut d: Dungeon) -> Dungeon {
+    let player = (d.player.position_x, d.player.position_y);
+    for i in 0..d.monsters.len() {
+        let monster = d.monsters[i];
+        if !monster.alive {
+            continue;
+        }
+        let (x, y) = (monster.pos_x, monster.pos_y);
+        let info = monster.kind.info();
+        let distance = d.distance_map[x][y];
+
+        if !monster.awake {
+            if d.noise > 0 && distance <= d.noise / 2 + info.hearing {
+                d.monsters[i].awake = true;
+                d.monsters[i].last_seen = Some(player);
+            }
+            continue;
+        }
+
+        let heard = d.noise > 0 && distance <= d.noise + info.hearing;
+        if heard || sees_player(&d, x, y, info.sight_radius) {
+            d.monsters[i].last_seen = Some(player);
+        } else if monster.last_seen == Some((x, y)) {
+            d.monsters[i].last_seen = None;
+        }
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ascii_map::{export_map, load_map};
+    use crate::dungeon::{calculate_distance_map, monster_map, take_turn};
+
+    const HALL: &str = "\
+%%%%%%%%%%%%
+%@.........%
+%..........%
+%..........%
+%..........%
+%.....g....%
+%%%%%%%%%%%%
+";
+
+    /// `HALL` with the goblin asleep.
+    fn sleeping_goblin() -> Dungeon {
+        let mut d = load_map(HALL).expect("perception test map loads");
+        d.monsters[0].awake = false;
+        d
+    }
+
+    /// Move the player to a cell, make some noise there and let the
+    /// monsters have their turn.
+    fn step(mut d: Dungeon, to: (usize, usize), noise: u16) -> Dungeon {
+        (d.player.position_x, d.player.position_y) = to;
+        d = calculate_distance_map(monster_map(d));
+        d = make_noise(d, noise);
+        take_turn(d)
+    }
+
+    #[test]
+    fn sleeping_monster_lets_the_player_sneak_past() {
+        let mut d = sleeping_goblin();
+        for y in 2..11 {
+            d = step(d, (1, y), NOISE_WALK);
+        }
+        assert!(!d.monsters[0].awake);
+        assert_eq!((d.monsters[0].pos_x, d.monsters[0].pos_y), (5, 6));
+    }
+
+    #[test]
+    fn noise_in_hearing_range_wakes_a_sleeper() {
+        let d = step(sleeping_goblin(), (1, 1), NOISE_WALK);
+        assert!(!d.monsters[0].awake);
+        let d = step(d, (1, 1), NOISE_ATTACK);
+        assert!(d.monsters[0].awake);
+        assert_eq!(d.monsters[0].last_seen, Some((1, 1)));
+    }
+
+    #[test]
+    fn lost_player_is_looked_for_where_last_seen() {
+        let mut d = load_map(
+            "\
+%%%%%%%%%%%%
+%g.........%
+%%%%%%%%%%.%
+%%%%%%%%%%.%
+%%%%%%%%%%.%
+%%%%%%%%%%.%
+%%%%%%%%%%@%
+%%%%%%%%%%%%
+",
+        )
+        .expect("perception test map loads");
+        d.monsters[0].last_seen = Some((1, 5));
+        for _ in 0..4 {
+            d = take_turn(d);
+        }
+        assert_eq!(
+            export_map(&d),
+            "\
+%%%%%%%%%%%%
+%....g.....%
+%%%%%%%%%%.%
+%%%%%%%%%%.%
+%%%%%%%%%%.%
+%%%%%%%%%%.%
+%%%%%%%%%%@%
+%%%%%%%%%%%%
+"
+        );
+        // Nobody there, so the goblin gives up
+        d = take_turn(d);
+        assert_eq!(d.monsters[0].last_seen, None);
+        assert_eq!((d.monsters[0].pos_x, d.monsters[0].pos_y), (1, 5));
+    }
+}
@@ -1,21 +1,117 @@
+use crate::dungeon::character::{Talent, MAX_TALENTS};
+use crate::dungeon::equipment::{self, SLOTS};
+use crate::dungeon::hunger::Hunger;
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::spell::{Spell, MAX_SPELLS};
+use crate::dungeon::status::Statuses;
 use quicksilver::prelude::Color;
 #[derive(Copy, Clone)]
 pub struct Player {
     pub character: char,
+    /// Names of the race and class picked at character creation, empty for
+    /// a player who skipped it.
+    pub race: &'static str,
+    pub class: &'static str,
     pub position_x: usize,
     pub position_y: usize,
     pub hp: i32,
     pub max_hp: i32,
+    /// The player's own attack and defence; gear and hunger are added on
+    /// top, see `attack_power` and `defence_power`.
     pub attack: i32,
     pub defence: i32,
     pub alive: bool,
+    pub level: u32,
+    pub xp: u32,
+    /// Perks earned by levelling up but not chosen yet.
+    pub perks: u32,
+    /// Tenths of a hp healed a turn, see `rest::regenerate`.
+    pub regeneration: i32,
+    /// Tenths of a hp healed but not yet a whole one.
+    pub regen_progress: i32,
+    /// Turns of food left in the stomach, see `hunger::tick_hunger`.
+    pub nutrition: u32,
+    pub mana: i32,
+    pub max_mana: i32,
+    /// Tenths of a mana point regained but not yet a whole one.
+    pub mana_progress: i32,
+    pub spells: [Option<Spell>; MAX_SPELLS],
     pub color: Color,
-    pub target: (usize, usize)
+    pub target: (usize, usize),
+    pub inventory: [Option<ItemKind>; 10],
+    pub gold: u32,
+    /// What is worn in each slot, in `Slot::ALL` order.
+    pub equipment: [Option<ItemKind>; SLOTS],
+    pub statuses: Statuses,
+    pub talents: [Option<Talent>; MAX_TALENTS],
 }
 
 impl Player {
     pub fn process_combat(self, mon_attack: i32) -> i32 {
-        let hp_after = self.hp - (mon_attack / self.defence);
-        hp_after
+        self.hp - (mon_attack / self.defence_power())
+    }
+
+    pub fn has_talent(self, talent: Talent) -> bool {
+        self.talents.contains(&Some(talent))
+    }
+
+    pub fn hunger(self) -> Hunger {
+        Hunger::of(self.nutrition)
+    }
+
+    /// Attack and defence added by everything equipped.
+    pub fn gear_bonus(self) -> (i32, i32) {
+        self.equipment
+            .iter()
+            .flatten()
+            .filter_map(|&item| equipment::gear_info(item))
+            .fold((0, 0), |(attack, defence), gear| {
+                (attack + gear.attack, defence + gear.defence)
+            })
+    }
+
+    /// Attack after gear and hunger are taken into account.
+    pub fn attack_power(self) -> i32 {
+        (self.attack + self.gear_bonus().0 + self.hunger().info().attack).max(1)
+    }
+
+    /// Defence after gear and hunger are taken into account.
+    pub fn defence_power(self) -> i32 {
+        (self.defence + self.gear_bonus().1 + self.hunger().info().defence).max(1)
+    }
+
+    /// Digging power of the best digging tool carried.
+    pub fn dig_power(self) -> u8 {
+        self.inventory
+            .iter()
+            .flatten()
+            .map(|item| item.dig_power())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn has_item(self, kind: ItemKind) -> bool {
+        self.inventory.contains(&Some(kind))
+    }
+
+    /// Put an item in the first free inventory slot, returns false when full.
+    pub fn add_item(&mut self, kind: ItemKind) -> bool {
+        match self.inventory.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(kind);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_item(&mut self, kind: ItemKind) -> bool {
+        match self.inventory.iter_mut().find(|slot| **slot == Some(kind)) {
+            Some(slot) => {
+                *slot = None;
+                true
+            }
+            None => false,
+        }
     }
 }
@@ -0,0 +1,203 @@
+use crate::dungeon::boss;
+use crate::dungeon::event::Event;
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::{drop_item, furthest_free_floor, push_event, Dungeon};
+
+/// What the player has to do to win.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Objective {
+    /// Fetch the ancient amulet from the deepest level and carry it back up
+    /// the stairs out of the dungeon.
+    Amulet,
+    /// Kill the ogre king, who rules the deepest level.
+    Boss,
+}
+
+pub struct ObjectiveInfo {
+    /// What `--objective` takes.
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+// One entry per `Objective` variant, in declaration order.
+const OBJECTIVE_TABLE: [ObjectiveInfo; 2] = [
+    ObjectiveInfo {
+        name: "amulet",
+        description: "bring the ancient amulet up from the deepest level",
+    },
+    ObjectiveInfo {
+        name: "boss",
+        description: "kill the ogre king on the deepest level",
+    },
+];
+
+impl Objective {
+    pub const ALL: [Objective; 2] = [Objective::Amulet, Objective::Boss];
+
+    pub fn info(self) -> &'static ObjectiveInfo {
+        &OBJECTIVE_TABLE[self as usize]
+    }
+
+    pub fn from_name(name: &str) -> Option<Objective> {
+        Objective::ALL
+            .iter()
+            .copied()
+            .find(|o| o.info().name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// The goal of a game and how deep the dungeon goes.
+#[derive(Copy, Clone)]
+pub struct Quest {
+    pub objective: Objective,
+    /// Depth of the bottom level, which has no way further down and is
+    /// where the boss and the amulet are.
+    pub deepest: u32,
+}
+
+pub const DEFAULT_QUEST: Quest = Quest {
+    objective: Objective::Amulet,
+    deepest: 4,
+};
+
+/// What the player got up to over the whole game, shown when they win.
+#[derive(Copy, Clone, Default)]
+pub struct RunStats {
+    /// Monsters the player killed themselves.
+    pub kills: u32,
+    /// Gold picked up, whatever was spent since.
+    pub gold_found: u32,
+    pub traps_found: u32,
+    pub deepest_reached: u32,
+}
+
+pub fn is_deepest(d: &Dungeon) -> bool {
+    d.depth >= d.quest.deepest
+}
+
+/// Leave the amulet on the deepest level as far from the player as it goes,
+/// for the amulet quest.
+pub fn place_amulet(d: Dungeon) -> Dungeon {
+    if d.quest.objective != Objective::Amulet || !is_deepest(&d) {
+        return d;
+    }
+    match furthest_free_floor(&d) {
+        Some((x, y)) => drop_item(d, ItemKind::AncientAmulet, x, y).0,
+        None => d,
+    }
+}
+
+/// The player wins the boss quest by seeing the boss of the deepest level
+/// dead, however it died.
+pub fn check_boss(mut d: Dungeon, monster: usize) -> Dungeon {
+    let boss = boss::boss_info(d.monsters[monster].kind).is_some();
+    if d.won || d.quest.objective != Objective::Boss || !boss || !is_deepest(&d) {
+        return d;
+    }
+    d.won = true;
+    push_event(d, Event::Victory)
+}
+
+/// The player climbs the first level's stairs up to daylight, which wins
+/// the amulet quest if they carry it. Otherwise they turn back.
+pub fn reach_surface(mut d: Dungeon) -> (Dungeon, bool) {
+    let done = match d.quest.objective {
+        Objective::Amulet => d.player.has_item(ItemKind::AncientAmulet),
+        Objective::Boss => false,
+    };
+    if !done {
+        let objective = d.quest.objective;
+        return (push_event(d, Event::QuestUnfinished { objective }), false);
+    }
+    d.won = true;
+    (push_event(d, Event::Victory), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ascii_map::load_map;
+    use crate::dungeon::terrain::Terrain;
+    use crate::dungeon::{hurt_monster, take_stairs};
+
+    const LEVEL: &str = "\
+%%%%%%%
+%@..O.%
+%%%%%%%
+";
+
+    /// `LEVEL` at a depth of a four level quest, with the player on the
+    /// stairs up.
+    fn level(objective: Objective, depth: u32) -> Dungeon {
+        let mut d = load_map(LEVEL).expect("quest test map loads");
+        d.quest = Quest {
+            objective,
+            deepest: 4,
+        };
+        d.depth = depth;
+        d.terrain_map[1][1] = Terrain::UpStairs;
+        d
+    }
+
+    fn kill_boss(d: Dungeon) -> Dungeon {
+        let boss = boss::find_boss(&d).expect("the ogre king is on the map");
+        hurt_monster(d, boss, d.monsters[boss].hp)
+    }
+
+    fn victories(d: &Dungeon) -> usize {
+        d.events
+            .iter()
+            .flatten()
+            .filter(|e| matches!(e, Event::Victory))
+            .count()
+    }
+
+    #[test]
+    fn carrying_the_amulet_out_wins() {
+        let mut d = level(Objective::Amulet, 1);
+        assert!(d.player.add_item(ItemKind::AncientAmulet));
+        let (d, moved) = take_stairs(d);
+        assert!(moved);
+        assert!(d.won);
+        assert_eq!(victories(&d), 1);
+    }
+
+    #[test]
+    fn leaving_without_the_amulet_turns_back() {
+        let (d, moved) = take_stairs(level(Objective::Amulet, 1));
+        assert!(!moved);
+        assert!(!d.won);
+        assert!(d
+            .events
+            .iter()
+            .flatten()
+            .any(|e| matches!(e, Event::QuestUnfinished { .. })));
+    }
+
+    #[test]
+    fn killing_the_deepest_boss_wins_the_boss_quest() {
+        let d = kill_boss(level(Objective::Boss, 4));
+        assert!(d.won);
+        assert_eq!(victories(&d), 1);
+    }
+
+    #[test]
+    fn killing_the_boss_leaves_other_quests_unfinished() {
+        assert!(!kill_boss(level(Objective::Amulet, 4)).won);
+        assert!(!kill_boss(level(Objective::Boss, 3)).won);
+    }
+
+    #[test]
+    fn amulet_only_lies_on_the_deepest_level() {
+        let amulets = |d: &Dungeon| {
+            d.items
+                .iter()
+                .flatten()
+                .filter(|i| i.kind == ItemKind::AncientAmulet)
+                .count()
+        };
+        assert_eq!(amulets(&place_amulet(level(Objective::Amulet, 4))), 1);
+        assert_eq!(amulets(&place_amulet(level(Objective::Amulet, 3))), 0);
+        assert_eq!(amulets(&place_amulet(level(Objective::Boss, 4))), 0);
+    }
+}
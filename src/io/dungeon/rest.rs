@@ -0,0 +1,113 @@
+use crate::dungeon::event::Event;
+use crate::dungeon::monster::MonsterKind;
+use crate::dungeon::player::Player;
+use crate::dungeon::status::StatusKind;
+use crate::dungeon::{calculate_distance_map, push_event, take_turn, Dungeon};
+
+/// Longest rest the player can take in one go.
+pub const MAX_REST_TURNS: u32 = 300;
+
+/// Heal the player by their regeneration, which is counted in tenths of a hp
+/// a turn. Poison and hunger past weakness stop healing, and nothing is
+/// banked at full health.
+pub fn regenerate(mut d: Dungeon) -> Dungeon {
+    let player = &mut d.player;
+    let starving = !player.hunger().info().heals;
+    if !player.alive || starving || poisoned(player) || player.hp >= player.max_hp {
+        player.regen_progress = 0;
+        return d;
+    }
+    player.regen_progress += player.regeneration;
+    player.hp = (player.hp + player.regen_progress / 10).min(player.max_hp);
+    player.regen_progress %= 10;
+    d
+}
+
+fn poisoned(player: &Player) -> bool {
+    player.statuses.has(StatusKind::Poison)
+}
+
+/// A hostile monster the player can see, if there is one. Monsters are
+/// visible along any clear line, as they are drawn.
+pub fn monster_in_view(d: &Dungeon) -> Option<MonsterKind> {
+    let player = (d.player.position_x, d.player.position_y);
+    d.monsters
+        .iter()
+        .find(|m| m.alive && !m.peaceful && d.line_of_sight(player, (m.pos_x, m.pos_y)))
+        .map(|m| m.kind)
+}
+
+/// Pass turns until the player is back to full health and mana, or until a
+/// monster comes into view, something hurts them or they get hungrier.
+/// Resting is refused with a monster already in sight, and when the player
+/// can't heal, which would only make them hungrier.
+pub fn rest(mut d: Dungeon) -> Dungeon {
+    if !d.player.alive {
+        return d;
+    }
+    if !d.player.hunger().info().heals {
+        return push_event(d, Event::TooHungryToRest);
+    }
+    if poisoned(&d.player) {
+        return push_event(d, Event::PoisonedCantRest);
+    }
+    if let Some(kind) = monster_in_view(&d) {
+        return push_event(d, Event::RestInterrupted { kind: Some(kind) });
+    }
+    let mut turns = 0;
+    while (d.player.hp < d.player.max_hp || d.player.mana < d.player.max_mana)
+        && turns < MAX_REST_TURNS
+    {
+        let (hp, hunger) = (d.player.hp, d.player.hunger());
+        d = take_turn(d);
+        d = calculate_distance_map(d);
+        turns += 1;
+        if !d.player.alive {
+            return d;
+        }
+        if let Some(kind) = monster_in_view(&d) {
+            return push_event(d, Event::RestInterrupted { kind: Some(kind) });
+        }
+        if d.player.hp < hp {
+            return push_event(d, Event::RestInterrupted { kind: None });
+        }
+        if d.player.hunger() > hunger {
+            // The player has already been told they are hungrier
+            return d;
+        }
+    }
+    push_event(d, Event::Rested { turns })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ascii_map::load_map;
+    use crate::dungeon::take_events;
+
+    const ROOM: &str = "\
+%%%%%
+%@..%
+%...%
+%%%%%
+";
+
+    #[test]
+    fn rests_back_to_full_health() {
+        let mut d = load_map(ROOM).expect("rest test map loads");
+        d.player.hp = 50;
+        let (d, events) = take_events(rest(d));
+        assert_eq!(d.player.hp, d.player.max_hp);
+        assert!(events.iter().any(|e| matches!(e, Event::Rested { .. })));
+    }
+
+    #[test]
+    fn too_hungry_to_rest() {
+        let mut d = load_map(ROOM).expect("rest test map loads");
+        d.player.hp = 50;
+        d.player.nutrition = 50;
+        let (d, events) = take_events(rest(d));
+        assert_eq!(d.turn, 0);
+        assert!(events.iter().any(|e| matches!(e, Event::TooHungryToRest)));
+    }
+}
@@ -0,0 +1,379 @@
+use crate::dungeon::event::Event;
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::loot;
+use crate::dungeon::monster::{Monster, MonsterKind, UNPLACED};
+use crate::dungeon::terrain::{Terrain, IMPASSABLE};
+use crate::dungeon::{drop_item, push_event, Dungeon};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// Mixed into the seed for the size of gold piles.
+const GOLD_SALT: u64 = 0x901d;
+
+/// Gold in a pile for each level of depth, at least and at most.
+const MIN_GOLD: u32 = 5;
+const MAX_GOLD: u32 = 20;
+
+/// A room of goods minded by a peaceful shopkeeper. Anything lying in the
+/// room is for sale while the shopkeeper is alive and calm.
+#[derive(Copy, Clone)]
+pub struct Shop {
+    /// The shop's room, as (x, y, length, width) like the generator's room
+    /// lists.
+    pub room: (usize, usize, usize, usize),
+    /// Roster slot of the shopkeeper.
+    pub keeper: usize,
+    /// Goods the player has picked up but not paid for yet.
+    pub unpaid: [Option<ItemKind>; 10],
+}
+
+impl Shop {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        let (rx, ry, length, width) = self.room;
+        (rx..rx + width).contains(&x) && (ry..ry + length).contains(&y)
+    }
+
+    /// What the player owes for the goods they picked up.
+    pub fn debt(&self) -> u32 {
+        self.unpaid.iter().flatten().map(|item| item.price()).sum()
+    }
+}
+
+/// What a shop pays for an item, 0 if it won't buy it.
+pub fn sell_price(kind: ItemKind) -> u32 {
+    kind.price() / 2
+}
+
+/// The shop, while its keeper is alive and hasn't been crossed.
+pub fn open_shop(d: &Dungeon) -> Option<Shop> {
+    let shop = d.shop?;
+    let keeper = d.monsters[shop.keeper];
+    (keeper.alive && keeper.peaceful).then_some(shop)
+}
+
+/// Whether the player is in an open shop, and so can buy and sell.
+pub fn can_trade(d: &Dungeon) -> bool {
+    open_shop(d).is_some_and(|shop| shop.contains(d.player.position_x, d.player.position_y))
+}
+
+/// Item slots of the goods lying in an open shop, gold aside.
+pub fn goods(d: &Dungeon) -> Vec<usize> {
+    let Some(shop) = open_shop(d) else {
+        return Vec::new();
+    };
+    (0..d.items.len())
+        .filter(|&i| {
+            d.items[i].is_some_and(|item| {
+                item.kind != ItemKind::Gold && shop.contains(item.pos_x, item.pos_y)
+            })
+        })
+        .collect()
+}
+
+/// Turn a plain room far enough from the player into a shop: a shopkeeper
+/// at the back and goods from the shop loot table. Returns the room used,
+/// if any room would do.
+pub fn place_shop(
+    mut d: Dungeon,
+    rooms: &[(usize, usize, usize, usize)],
+    min_player_distance: u16,
+    rng: &mut StdRng,
+) -> (Dungeon, Option<(usize, usize, usize, usize)>) {
+    let Some(slot) = d.monsters.iter().position(|m| m.pos_x == UNPLACED) else {
+        return (d, None);
+    };
+    let mut rooms = rooms.to_vec();
+    rooms.shuffle(rng);
+    for room in rooms {
+        let (x, y, length, width) = room;
+        let player = (d.player.position_x, d.player.position_y);
+        // The back of the room, so the shopkeeper doesn't block the way in
+        let back = (x..x + width)
+            .flat_map(|cx| (y..y + length).map(move |cy| (cx, cy)))
+            .filter(|&(cx, cy)| {
+                d.terrain_map[cx][cy] == Terrain::Floor
+                    && d.distance_map[cx][cy] < IMPASSABLE
+                    && !d.monsters.iter().any(|m| (m.pos_x, m.pos_y) == (cx, cy))
+                    && (cx, cy) != player
+            })
+            .max_by_key(|&(cx, cy)| d.distance_map[cx][cy]);
+        let Some((kx, ky)) = back else {
+            continue;
+        };
+        let in_room = (x..x + width).contains(&player.0) && (y..y + length).contains(&player.1);
+        if in_room || d.distance_map[kx][ky] < min_player_distance {
+            continue;
+        }
+
+        d.monsters[slot] = Monster::new(MonsterKind::Shopkeeper);
+        d.monsters[slot].pos_x = kx;
+        d.monsters[slot].pos_y = ky;
+        d.shop = Some(Shop {
+            room,
+            keeper: slot,
+            unpaid: [None; 10],
+        });
+        d = loot::fill_room(d, "shop", room, rng);
+        return (d, Some(room));
+    }
+    (d, None)
+}
+
+/// Put a gold pile the player stepped on into their purse.
+pub fn pick_up_gold(mut d: Dungeon) -> Dungeon {
+    let cell = ((d.player.position_x as u64) << 16) | d.player.position_y as u64;
+    let mut rng = StdRng::seed_from_u64(d.seed ^ ((d.turn as u64) << 8) ^ GOLD_SALT ^ cell);
+    let amount = rng.gen_range(MIN_GOLD..=MAX_GOLD) * d.depth;
+    d.player.gold += amount;
+    d.stats.gold_found += amount;
+    push_event(d, Event::GoldPickedUp { amount })
+}
+
+/// Note down goods the player picked up in an open shop, to be paid for
+/// before they leave.
+pub fn picked_up(mut d: Dungeon, kind: ItemKind, x: usize, y: usize) -> Dungeon {
+    let Some(mut shop) = open_shop(&d) else {
+        return d;
+    };
+    if !shop.contains(x, y) || kind.price() == 0 {
+        return d;
+    }
+    if let Some(slot) = shop.unpaid.iter_mut().find(|slot| slot.is_none()) {
+        *slot = Some(kind);
+    }
+    d.shop = Some(shop);
+    push_event(
+        d,
+        Event::Unpaid {
+            item: kind,
+            price: kind.price(),
+        },
+    )
+}
+
+/// Make a peaceful monster turn on the player.
+pub fn provoke(mut d: Dungeon, monster: usize) -> Dungeon {
+    if !d.monsters[monster].peaceful {
+        return d;
+    }
+    d.monsters[monster].peaceful = false;
+    d.monsters[monster].awake = true;
+    d.monsters[monster].last_seen = Some((d.player.position_x, d.player.position_y));
+    let kind = d.monsters[monster].kind;
+    push_event(d, Event::MonsterProvoked { kind })
+}
+
+/// A player who walks out of an open shop with unpaid goods has robbed it,
+/// and the shopkeeper comes after them. Goods from a closed shop are free
+/// for the taking.
+pub fn check_theft(mut d: Dungeon) -> Dungeon {
+    let Some(mut shop) = d.shop else {
+        return d;
+    };
+    if shop.unpaid.iter().all(|slot| slot.is_none()) {
+        return d;
+    }
+    if open_shop(&d).is_none() {
+        shop.unpaid = [None; 10];
+        d.shop = Some(shop);
+        return d;
+    }
+    if shop.contains(d.player.position_x, d.player.position_y) {
+        return d;
+    }
+    let debt = shop.debt();
+    shop.unpaid = [None; 10];
+    d.shop = Some(shop);
+    d = push_event(d, Event::LeftWithoutPaying { debt });
+    provoke(d, shop.keeper)
+}
+
+/// Buy the goods in an item slot, straight into the pack.
+pub fn buy(mut d: Dungeon, slot: usize) -> Dungeon {
+    if !can_trade(&d) || !goods(&d).contains(&slot) {
+        return d;
+    }
+    let Some(item) = d.items[slot] else {
+        return d;
+    };
+    let price = item.kind.price();
+    if d.player.gold < price {
+        return push_event(d, Event::CantAfford { price });
+    }
+    if !d.player.add_item(item.kind) {
+        return push_event(d, Event::PackFull);
+    }
+    d.player.gold -= price;
+    d.items[slot] = None;
+    push_event(
+        d,
+        Event::Bought {
+            item: item.kind,
+            price,
+        },
+    )
+}
+
+/// Sell a carried item to the shop, which puts it out on its floor. Unpaid
+/// goods are put back instead.
+pub fn sell(mut d: Dungeon, kind: ItemKind) -> Dungeon {
+    let Some(mut shop) = open_shop(&d) else {
+        return d;
+    };
+    if !can_trade(&d) || !d.player.has_item(kind) {
+        return d;
+    }
+    let unpaid = shop.unpaid.iter().position(|slot| *slot == Some(kind));
+    let price = sell_price(kind);
+    if unpaid.is_none() && price == 0 {
+        return push_event(d, Event::NotInterested { item: kind });
+    }
+    let (x, y, length, width) = shop.room;
+    let cell = (x..x + width)
+        .flat_map(|cx| (y..y + length).map(move |cy| (cx, cy)))
+        .find(|&(cx, cy)| {
+            d.terrain_map[cx][cy].passable()
+                && !d
+                    .monsters
+                    .iter()
+                    .any(|m| m.alive && (m.pos_x, m.pos_y) == (cx, cy))
+                && !d
+                    .items
+                    .iter()
+                    .flatten()
+                    .any(|i| (i.pos_x, i.pos_y) == (cx, cy))
+        });
+    let Some((cx, cy)) = cell else {
+        return push_event(d, Event::ShopFull);
+    };
+    let (with_item, dropped) = drop_item(d, kind, cx, cy);
+    d = with_item;
+    if !dropped {
+        return push_event(d, Event::ShopFull);
+    }
+    d.player.remove_item(kind);
+    if let Some(i) = unpaid {
+        shop.unpaid[i] = None;
+        d.shop = Some(shop);
+        return push_event(d, Event::PutBack { item: kind });
+    }
+    d.player.gold += price;
+    push_event(d, Event::Sold { item: kind, price })
+}
+
+/// Pay for every unpaid item at once.
+pub fn pay(mut d: Dungeon) -> Dungeon {
+    let Some(mut shop) = open_shop(&d) else {
+        return d;
+    };
+    let debt = shop.debt();
+    if debt == 0 || !can_trade(&d) {
+        return d;
+    }
+    if d.player.gold < debt {
+        return push_event(d, Event::CantAfford { price: debt });
+    }
+    d.player.gold -= debt;
+    shop.unpaid = [None; 10];
+    d.shop = Some(shop);
+    push_event(d, Event::Paid { amount: debt })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ascii_map::load_map;
+    use crate::dungeon::{hurt_monster, pick_up_items};
+
+    /// The shop is the right half, with a sword for sale.
+    const SHOP: &str = "\
+%%%%%%%%%%
+%.....@.S%
+%......|.%
+%%%%%%%%%%
+";
+
+    fn shop() -> Dungeon {
+        let mut d = load_map(SHOP).expect("shop test map loads");
+        d.player.inventory = [None; 10];
+        d.shop = Some(Shop {
+            room: (1, 5, 4, 2),
+            keeper: 0,
+            unpaid: [None; 10],
+        });
+        assert!(d.monsters[0].peaceful);
+        d
+    }
+
+    fn sword(d: &Dungeon) -> Option<usize> {
+        d.items
+            .iter()
+            .position(|i| i.is_some_and(|i| i.kind == ItemKind::Sword))
+    }
+
+    fn happened(d: &Dungeon, event: impl Fn(&Event) -> bool) -> bool {
+        d.events.iter().flatten().any(event)
+    }
+
+    #[test]
+    fn buying_without_the_gold_is_refused() {
+        let mut d = shop();
+        d.player.gold = ItemKind::Sword.price() - 1;
+        let slot = sword(&d).unwrap();
+        let d = buy(d, slot);
+        assert!(!d.player.has_item(ItemKind::Sword));
+        assert_eq!(sword(&d), Some(slot));
+        assert_eq!(d.player.gold, ItemKind::Sword.price() - 1);
+        assert!(happened(&d, |e| matches!(e, Event::CantAfford { .. })));
+    }
+
+    #[test]
+    fn buying_trades_gold_for_the_item() {
+        let mut d = shop();
+        d.player.gold = ItemKind::Sword.price() + 5;
+        let d = buy(d, sword(&d).unwrap());
+        assert!(d.player.has_item(ItemKind::Sword));
+        assert_eq!(sword(&d), None);
+        assert_eq!(d.player.gold, 5);
+    }
+
+    #[test]
+    fn selling_pays_the_sell_price() {
+        let mut d = shop();
+        d.items = [None; 40];
+        assert!(d.player.add_item(ItemKind::Sword));
+        let d = sell(d, ItemKind::Sword);
+        assert!(!d.player.has_item(ItemKind::Sword));
+        assert_eq!(d.player.gold, sell_price(ItemKind::Sword));
+        let item = d.items[sword(&d).unwrap()].unwrap();
+        assert!(d.shop.unwrap().contains(item.pos_x, item.pos_y));
+    }
+
+    #[test]
+    fn walking_out_with_unpaid_goods_provokes_the_keeper() {
+        let mut d = shop();
+        (d.player.position_x, d.player.position_y) = (2, 7);
+        d = check_theft(pick_up_items(d));
+        assert_eq!(d.shop.unwrap().debt(), ItemKind::Sword.price());
+        assert!(d.monsters[0].peaceful);
+
+        (d.player.position_x, d.player.position_y) = (1, 2);
+        d = check_theft(d);
+        assert!(!d.monsters[0].peaceful);
+        assert!(happened(&d, |e| matches!(
+            e,
+            Event::LeftWithoutPaying { .. }
+        )));
+        assert!(open_shop(&d).is_none());
+    }
+
+    #[test]
+    fn attacking_the_keeper_turns_it_hostile() {
+        let d = hurt_monster(shop(), 0, 1);
+        assert!(!d.monsters[0].peaceful);
+        assert!(d.monsters[0].awake);
+        assert!(happened(&d, |e| matches!(e, Event::MonsterProvoked { .. })));
+        assert!(!can_trade(&d));
+    }
+}
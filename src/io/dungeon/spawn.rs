@@ -0,0 +1,348 @@
+use crate::dungeon::boss;
+use crate::dungeon::monster::{Monster, MonsterKind, UNPLACED};
+use crate::dungeon::terrain::{Terrain, IMPASSABLE};
+use crate::dungeon::Dungeon;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::fmt;
+
+/// How many random cells to try for each monster before giving up on it.
+const SPAWN_ATTEMPTS: usize = 2000;
+
+/// Chance that a monster starts the level asleep.
+const ASLEEP_CHANCE: f64 = 0.6;
+
+/// How far (in steps either way) from their leader followers are placed.
+const ESCORT_RADIUS: usize = 2;
+
+/// Where monsters are allowed to start a level.
+pub struct SpawnRules {
+    /// Smallest walking distance (distance map cost) from the player.
+    pub min_player_distance: u16,
+    /// Only spawn on room floor, never in corridors or doorways.
+    pub rooms_only: bool,
+    /// Most monsters a single room may start with. A pack counts once.
+    pub max_per_room: usize,
+}
+
+pub const DEFAULT_SPAWN_RULES: SpawnRules = SpawnRules {
+    min_player_distance: 8,
+    rooms_only: true,
+    max_per_room: 3,
+};
+
+/// What the spawner actually did on one level, checked against the rules.
+pub struct SpawnReport {
+    pub seed: u64,
+    pub placed: usize,
+    pub not_placed: usize,
+    pub nearest_to_player: u16,
+    pub stacked: usize,
+    pub outside_rooms: usize,
+    pub busiest_room: usize,
+}
+
+impl SpawnReport {
+    pub fn violations(&self, rules: &SpawnRules) -> Vec<String> {
+        let mut violations = Vec::new();
+        if self.placed > 0 && self.nearest_to_player < rules.min_player_distance {
+            violations.push(format!(
+                "monster {} steps from the player",
+                self.nearest_to_player
+            ));
+        }
+        if self.stacked > 0 {
+            violations.push(format!("{} monsters share a cell", self.stacked));
+        }
+        if rules.rooms_only && self.outside_rooms > 0 {
+            violations.push(format!("{} monsters outside rooms", self.outside_rooms));
+        }
+        if self.busiest_room > rules.max_per_room {
+            violations.push(format!("{} monsters in one room", self.busiest_room));
+        }
+        violations
+    }
+}
+
+impl fmt::Display for SpawnReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "seed {}: {} placed, {} not placed, nearest {} steps, {} stacked, {} outside rooms, busiest room {}",
+            self.seed,
+            self.placed,
+            self.not_placed,
+            self.nearest_to_player,
+            self.stacked,
+            self.outside_rooms,
+            self.busiest_room
+        )
+    }
+}
+
+/// Label every room floor cell with a room number; 0 is not a room.
+/// Rooms are the 4-connected patches of floor, corridors and doors split them.
+pub fn room_ids(d: &Dungeon) -> [[usize; 80]; 30] {
+    let mut ids = [[0; 80]; 30];
+    let mut next_id = 1;
+    for x in 0..d.dungeon_x {
+        for y in 0..d.dungeon_y {
+            if d.terrain_map[x][y] != Terrain::Floor || ids[x][y] != 0 {
+                continue;
+            }
+            ids[x][y] = next_id;
+            let mut stack = vec![(x, y)];
+            while let Some((cx, cy)) = stack.pop() {
+                for (nx, ny) in [(cx - 1, cy), (cx + 1, cy), (cx, cy - 1), (cx, cy + 1)] {
+                    if d.terrain_map[nx][ny] == Terrain::Floor && ids[nx][ny] == 0 {
+                        ids[nx][ny] = next_id;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            next_id += 1;
+        }
+    }
+    ids
+}
+
+/// Whether a monster may start the level on (x, y), apart from the room cap.
+fn free_cell(
+    d: &Dungeon,
+    rules: &SpawnRules,
+    rooms: &[[usize; 80]; 30],
+    x: usize,
+    y: usize,
+) -> bool {
+    let occupied = (x == d.player.position_x && y == d.player.position_y)
+        || d.monsters.iter().any(|m| m.pos_x == x && m.pos_y == y);
+    d.terrain_map[x][y].passable()
+        && !occupied
+        && d.distance_map[x][y] >= rules.min_player_distance
+        && (!rules.rooms_only || rooms[x][y] != 0)
+}
+
+/// Put a leader's followers in free roster slots close around it, in the
+/// same room. Followers that don't fit are left out.
+fn place_escort(
+    mut d: Dungeon,
+    leader: usize,
+    rules: &SpawnRules,
+    rooms: &[[usize; 80]; 30],
+    rng: &mut StdRng,
+) -> Dungeon {
+    let Some((kind, count)) = d.monsters[leader].kind.info().escort else {
+        return d;
+    };
+    let (lx, ly) = (d.monsters[leader].pos_x, d.monsters[leader].pos_y);
+    d.monsters[leader].pack = Some(leader);
+
+    for _ in 0..count {
+        let Some(slot) = d.monsters.iter().position(|m| m.pos_x == UNPLACED) else {
+            break;
+        };
+        let cells: Vec<(usize, usize)> = (lx.saturating_sub(ESCORT_RADIUS)..=lx + ESCORT_RADIUS)
+            .flat_map(|x| {
+                (ly.saturating_sub(ESCORT_RADIUS)..=ly + ESCORT_RADIUS).map(move |y| (x, y))
+            })
+            .filter(|&(x, y)| x < d.dungeon_x && y < d.dungeon_y)
+            .filter(|&(x, y)| rooms[x][y] == rooms[lx][ly] && free_cell(&d, rules, rooms, x, y))
+            .collect();
+        if cells.is_empty() {
+            break;
+        }
+        let (x, y) = cells[rng.gen_range(0..cells.len())];
+        d.monsters[slot] = Monster::new(kind);
+        d.monsters[slot].pos_x = x;
+        d.monsters[slot].pos_y = y;
+        d.monsters[slot].awake = d.monsters[leader].awake;
+        d.monsters[slot].pack = Some(leader);
+    }
+    d
+}
+
+/// Fill the free roster slots with random monsters, following the rules.
+/// Slots that find no legal cell are taken out of play.
+pub fn place_monsters(mut d: Dungeon, rules: &SpawnRules, rng: &mut StdRng) -> Dungeon {
+    let rooms = room_ids(&d);
+    let mut per_room = [0; 80 * 30];
+    for monster in d.monsters.iter().filter(|m| m.pos_x != UNPLACED) {
+        let room = rooms[monster.pos_x][monster.pos_y];
+        per_room[room] += 1;
+        // A boss keeps its room to itself
+        if room != 0 && boss::boss_info(monster.kind).is_some() {
+            per_room[room] = per_room[room].max(rules.max_per_room);
+        }
+    }
+
+    for mon in 0..d.monsters.len() {
+        // Vaults may already have filled some of the roster
+        if d.monsters[mon].pos_x != UNPLACED {
+            continue;
+        }
+
+        let mut placed = false;
+        for _ in 0..SPAWN_ATTEMPTS {
+            let x = rng.gen_range(1..d.dungeon_x);
+            let y = rng.gen_range(1..d.dungeon_y);
+            let room = rooms[x][y];
+            let legal = free_cell(&d, rules, &rooms, x, y)
+                && (room == 0 || per_room[room] < rules.max_per_room);
+
+            if legal {
+                let kind = match rng.gen_range(0..11) {
+                    0..=2 => MonsterKind::Rat,
+                    3 => MonsterKind::RockWorm,
+                    4 => MonsterKind::GoblinChief,
+                    5 => MonsterKind::Spitter,
+                    _ => MonsterKind::Goblin,
+                };
+                d.monsters[mon] = Monster::new(kind);
+                d.monsters[mon].pos_x = x;
+                d.monsters[mon].pos_y = y;
+                d.monsters[mon].awake = !rng.gen_bool(ASLEEP_CHANCE);
+                d = place_escort(d, mon, rules, &rooms, rng);
+                per_room[room] += 1;
+                placed = true;
+                break;
+            }
+        }
+        if !placed {
+            d.monsters[mon].park();
+        }
+    }
+    d
+}
+
+pub fn spawn_report(d: &Dungeon) -> SpawnReport {
+    let rooms = room_ids(d);
+    let mut per_room = [0; 80 * 30];
+    let mut report = SpawnReport {
+        seed: d.seed,
+        placed: 0,
+        not_placed: 0,
+        nearest_to_player: IMPASSABLE,
+        stacked: 0,
+        outside_rooms: 0,
+        busiest_room: 0,
+    };
+
+    for (i, monster) in d.monsters.iter().enumerate() {
+        if !monster.alive {
+            report.not_placed += 1;
+            continue;
+        }
+        let (x, y) = (monster.pos_x, monster.pos_y);
+        report.placed += 1;
+        report.nearest_to_player = report.nearest_to_player.min(d.distance_map[x][y]);
+
+        let on_player = x == d.player.position_x && y == d.player.position_y;
+        let shared = d.monsters[..i]
+            .iter()
+            .any(|m| m.alive && m.pos_x == x && m.pos_y == y);
+        if on_player || shared {
+            report.stacked += 1;
+        }
+
+        let follower = monster.pack.is_some_and(|leader| leader != i);
+        if rooms[x][y] == 0 {
+            report.outside_rooms += 1;
+        } else if !follower {
+            per_room[rooms[x][y]] += 1;
+            report.busiest_room = report.busiest_room.max(per_room[rooms[x][y]]);
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::quest::{Objective, Quest};
+    use crate::dungeon::{generate_dungeon_with, generate_level};
+
+    fn assert_keeps(d: &Dungeon, rules: &SpawnRules) {
+        let report = spawn_report(d);
+        let violations = report.violations(rules);
+        assert!(
+            violations.is_empty(),
+            "depth {} {}\n  {}",
+            d.depth,
+            report,
+            violations.join("\n  ")
+        );
+    }
+
+    #[test]
+    fn first_levels_keep_the_rules() {
+        for seed in 0..100 {
+            assert_keeps(
+                &generate_dungeon_with(seed, &DEFAULT_SPAWN_RULES),
+                &DEFAULT_SPAWN_RULES,
+            );
+        }
+    }
+
+    #[test]
+    fn every_depth_keeps_the_rules() {
+        let quest = Quest {
+            objective: Objective::Boss,
+            deepest: 4,
+        };
+        for seed in 0..25 {
+            for depth in 1..=quest.deepest {
+                let d = generate_level(seed, depth, quest, &DEFAULT_SPAWN_RULES);
+                assert_keeps(&d, &DEFAULT_SPAWN_RULES);
+            }
+        }
+    }
+
+    #[test]
+    fn boss_has_a_room_to_itself() {
+        let quest = Quest {
+            objective: Objective::Boss,
+            deepest: 2,
+        };
+        for seed in 0..100 {
+            let d = generate_level(seed, quest.deepest, quest, &DEFAULT_SPAWN_RULES);
+            let rooms = room_ids(&d);
+            let boss = boss::find_boss(&d).expect("the deepest level has its boss");
+            let room = rooms[d.monsters[boss].pos_x][d.monsters[boss].pos_y];
+            let sharing = d
+                .monsters
+                .iter()
+                .enumerate()
+                .filter(|&(i, m)| i != boss && m.alive && rooms[m.pos_x][m.pos_y] == room)
+                .count();
+            assert_eq!(
+                sharing, 0,
+                "seed {}: {} monsters in the boss room",
+                seed, sharing
+            );
+        }
+    }
+
+    #[test]
+    fn stricter_rules_are_kept() {
+        let rules = SpawnRules {
+            min_player_distance: 15,
+            rooms_only: true,
+            max_per_room: 1,
+        };
+        for seed in 0..50 {
+            assert_keeps(&generate_dungeon_with(seed, &rules), &rules);
+        }
+    }
+
+    #[test]
+    fn most_monsters_find_a_place() {
+        let not_placed: usize = (0..50)
+            .map(|seed| spawn_report(&generate_dungeon_with(seed, &DEFAULT_SPAWN_RULES)).not_placed)
+            .sum();
+        assert!(
+            not_placed < 50,
+            "{} monsters could not be placed",
+            not_placed
+        );
+    }
+}
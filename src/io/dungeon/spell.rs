@@ -0,0 +1,371 @@
+use crate::dungeon::event::Event;
+use crate::dungeon::status::{Status, StatusKind};
+use crate::dungeon::trap;
+use crate::dungeon::{dig, hurt_monster, line, pick_up_items, push_event, Dungeon};
+
+/// Most spells the player's spellbook can hold.
+pub const MAX_SPELLS: usize = 5;
+
+/// Tenths of a mana point the player gets back each turn.
+const MANA_REGENERATION: i32 = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Spell {
+    Bolt,
+    Blast,
+    Heal,
+    Blink,
+    Slow,
+}
+
+/// What a spell does once cast. Ranges count cells in any direction, like
+/// monster reach.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpellEffect {
+    /// Fly along a line towards the target, hurting the first monster in the
+    /// way. Anything opaque stops it, and rock it hits is dug at with
+    /// `dig_power`.
+    Bolt {
+        damage: i32,
+        range: usize,
+        dig_power: u8,
+    },
+    /// Hurt every monster within `radius` of a target cell in sight.
+    Blast {
+        damage: i32,
+        radius: usize,
+        range: usize,
+    },
+    /// Heal the player.
+    Heal { amount: i32 },
+    /// Move the player to a free cell in sight.
+    Blink { range: usize },
+    /// Slow a monster in sight for some turns.
+    Slow { turns: u32, range: usize },
+}
+
+pub struct SpellInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub mana: i32,
+    pub effect: SpellEffect,
+}
+
+// One entry per `Spell` variant, in declaration order.
+const SPELL_TABLE: [SpellInfo; 5] = [
+    SpellInfo {
+        name: "Bolt",
+        description: "a bolt of force that stops at the first thing it hits",
+        mana: 5,
+        effect: SpellEffect::Bolt {
+            damage: 20,
+            range: 8,
+            dig_power: 60,
+        },
+    },
+    SpellInfo {
+        name: "Blast",
+        description: "an explosion hurting everything around the target",
+        mana: 12,
+        effect: SpellEffect::Blast {
+            damage: 15,
+            radius: 1,
+            range: 6,
+        },
+    },
+    SpellInfo {
+        name: "Heal",
+        description: "closes your wounds",
+        mana: 8,
+        effect: SpellEffect::Heal { amount: 30 },
+    },
+    SpellInfo {
+        name: "Blink",
+        description: "teleports you to a spot you can see",
+        mana: 6,
+        effect: SpellEffect::Blink { range: 6 },
+    },
+    SpellInfo {
+        name: "Slow",
+        description: "makes a monster lose every other turn",
+        mana: 4,
+        effect: SpellEffect::Slow {
+            turns: 10,
+            range: 8,
+        },
+    },
+];
+
+impl Spell {
+    pub const ALL: [Spell; 5] = [
+        Spell::Bolt,
+        Spell::Blast,
+        Spell::Heal,
+        Spell::Blink,
+        Spell::Slow,
+    ];
+
+    pub fn info(self) -> &'static SpellInfo {
+        &SPELL_TABLE[self as usize]
+    }
+
+    pub fn from_name(name: &str) -> Option<Spell> {
+        Spell::ALL
+            .iter()
+            .copied()
+            .find(|s| s.info().name.eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the spell needs a target cell picked before it is cast.
+    pub fn targeted(self) -> bool {
+        !matches!(self.info().effect, SpellEffect::Heal { .. })
+    }
+}
+
+fn monster_at(d: &Dungeon, x: usize, y: usize) -> Option<usize> {
+    d.monsters
+        .iter()
+        .position(|m| m.alive && m.pos_x == x && m.pos_y == y)
+}
+
+fn in_range(d: &Dungeon, target: (usize, usize), range: usize) -> bool {
+    let player = (d.player.position_x, d.player.position_y);
+    let reach = player.0.abs_diff(target.0).max(player.1.abs_diff(target.1));
+    reach <= range && d.line_of_sight(player, target)
+}
+
+/// Hurt a monster with a spell, letting it know where the player is.
+fn strike(mut d: Dungeon, spell: Spell, i: usize, damage: i32) -> Dungeon {
+    let kind = d.monsters[i].kind;
+    d.monsters[i].awake = true;
+    d.monsters[i].last_seen = Some((d.player.position_x, d.player.position_y));
+    d = push_event(
+        d,
+        Event::SpellHit {
+            spell,
+            kind,
+            damage,
+        },
+    );
+    hurt_monster(d, i, damage)
+}
+
+/// Cast a spell from the player's spellbook at a target cell, which untargeted
+/// spells ignore. Returns whether the spell was cast, using up the mana and
+/// the turn; the player is told why when it isn't.
+pub fn cast(mut d: Dungeon, spell: Spell, target: (usize, usize)) -> (Dungeon, bool) {
+    let info = spell.info();
+    if !d.player.alive || !d.player.spells.contains(&Some(spell)) {
+        return (d, false);
+    }
+    if d.player.mana < info.mana {
+        return (push_event(d, Event::NotEnoughMana { spell }), false);
+    }
+    let player = (d.player.position_x, d.player.position_y);
+    let fizzled = match info.effect {
+        // Bolts are aimed, and find out on the way whether they get there
+        SpellEffect::Bolt { range, .. } => {
+            let reach = player.0.abs_diff(target.0).max(player.1.abs_diff(target.1));
+            target == player || reach > range
+        }
+        SpellEffect::Blast { range, .. } => !in_range(&d, target, range),
+        SpellEffect::Heal { .. } => false,
+        SpellEffect::Blink { range } => {
+            !in_range(&d, target, range)
+                || !d.terrain_map[target.0][target.1].passable()
+                || monster_at(&d, target.0, target.1).is_some()
+        }
+        SpellEffect::Slow { range, .. } => {
+            !in_range(&d, target, range) || monster_at(&d, target.0, target.1).is_none()
+        }
+    };
+    if fizzled {
+        return (push_event(d, Event::SpellFizzled { spell }), false);
+    }
+
+    d.player.mana -= info.mana;
+    d = push_event(d, Event::SpellCast { spell });
+    match info.effect {
+        SpellEffect::Bolt {
+            damage, dig_power, ..
+        } => {
+            for (x, y) in line(player, target) {
+                if let Some(i) = monster_at(&d, x, y) {
+                    d = strike(d, spell, i, damage);
+                    break;
+                }
+                if !d.terrain_map[x][y].transparent() {
+                    d = dig(d, x, y, dig_power).0;
+                    break;
+                }
+            }
+        }
+        SpellEffect::Blast { damage, radius, .. } => {
+            for i in 0..d.monsters.len() {
+                let monster = d.monsters[i];
+                let cell = (monster.pos_x, monster.pos_y);
+                let reach = cell.0.abs_diff(target.0).max(cell.1.abs_diff(target.1));
+                if monster.alive && reach <= radius && d.line_of_sight(target, cell) {
+                    d = strike(d, spell, i, damage);
+                }
+            }
+        }
+        SpellEffect::Heal { amount } => {
+            d.player.hp = (d.player.hp + amount).min(d.player.max_hp);
+        }
+        SpellEffect::Blink { .. } => {
+            d.player.position_x = target.0;
+            d.player.position_y = target.1;
+            d = trap::enter_cell(pick_up_items(d));
+        }
+        SpellEffect::Slow { turns, .. } => {
+            if let Some(i) = monster_at(&d, target.0, target.1) {
+                d.monsters[i].statuses.add(Status {
+                    kind: StatusKind::Slow,
+                    turns,
+                    strength: 1,
+                    source: None,
+                });
+                d = strike(d, spell, i, 0);
+            }
+        }
+    }
+    (d, true)
+}
+
+/// Win back a little mana, counted in tenths like hp regeneration.
+pub fn regenerate_mana(mut d: Dungeon) -> Dungeon {
+    let player = &mut d.player;
+    if !player.alive || player.mana >= player.max_mana {
+        player.mana_progress = 0;
+        return d;
+    }
+    player.mana_progress += MANA_REGENERATION;
+    player.mana = (player.mana + player.mana_progress / 10).min(player.max_mana);
+    player.mana_progress %= 10;
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ability::MAX_ABILITIES;
+    use crate::dungeon::ascii_map::load_map;
+    use crate::dungeon::{monster_map, take_turn};
+
+    /// Load a map where the player knows every spell, cast one and play a
+    /// few more turns. Monsters know where the player is and keep their
+    /// abilities on cooldown, as in the movement tests.
+    fn run(map: &str, spell: Spell, target: (usize, usize), mana: i32, turns: usize) -> Dungeon {
+        let mut d = load_map(map).expect("spell test map loads");
+        let player = (d.player.position_x, d.player.position_y);
+        for monster in d.monsters.iter_mut().filter(|m| m.alive) {
+            monster.last_seen = Some(player);
+            monster.ability_cooldowns = [u32::MAX; MAX_ABILITIES];
+        }
+        d.player.spells = Spell::ALL.map(Some);
+        d.player.max_mana = 100;
+        d.player.mana = mana;
+        d = cast(d, spell, target).0;
+        for _ in 0..turns {
+            d = monster_map(d);
+            d = take_turn(d);
+        }
+        d
+    }
+
+    #[test]
+    fn bolt_hurts_the_first_monster_in_line() {
+        let map = "\
+%%%%%%%
+%@..OO%
+%%%%%%%
+";
+        let d = run(map, Spell::Bolt, (1, 5), 100, 0);
+        assert_eq!(d.monsters[0].hp, 40);
+        assert_eq!(d.monsters[1].hp, 60);
+        assert_eq!(d.player.mana, 95);
+    }
+
+    #[test]
+    fn walls_stop_bolts() {
+        let map = "\
+%%%%%%%
+%@.%.O%
+%%%%%%%
+";
+        let d = run(map, Spell::Bolt, (1, 5), 100, 0);
+        assert_eq!(d.monsters[0].hp, 60);
+        assert_eq!(d.player.mana, 95);
+    }
+
+    #[test]
+    fn bolts_chip_at_rock() {
+        let map = "\
+%%%%%%
+%@. .%
+%%%%%%
+";
+        let d = run(map, Spell::Bolt, (1, 4), 100, 0);
+        assert_eq!(d.hardness_map[1][3], 68);
+    }
+
+    #[test]
+    fn blast_catches_everything_around_the_target() {
+        let map = "\
+%%%%%%%%%
+%@...OO.%
+%....O..%
+%.......%
+%%%%%%%%%
+";
+        let d = run(map, Spell::Blast, (1, 5), 100, 0);
+        let hit = d.monsters.iter().filter(|m| m.alive && m.hp == 45).count();
+        assert_eq!(hit, 3);
+    }
+
+    #[test]
+    fn blink_moves_the_player() {
+        let map = "\
+%%%%%%%
+%@....%
+%%%%%%%
+";
+        let d = run(map, Spell::Blink, (1, 4), 100, 0);
+        assert_eq!((d.player.position_x, d.player.position_y), (1, 4));
+    }
+
+    #[test]
+    fn blink_needs_a_free_cell() {
+        let map = "\
+%%%%%%%
+%@..g.%
+%%%%%%%
+";
+        let d = run(map, Spell::Blink, (1, 4), 100, 0);
+        assert_eq!((d.player.position_x, d.player.position_y), (1, 1));
+        assert_eq!(d.player.mana, 100);
+    }
+
+    #[test]
+    fn slowed_monster_loses_every_other_turn() {
+        let map = "\
+%%%%%%%%%%
+%@......g%
+%%%%%%%%%%
+";
+        let d = run(map, Spell::Slow, (1, 8), 100, 4);
+        assert_eq!(d.monsters[0].pos_y, 6);
+    }
+
+    #[test]
+    fn no_spell_without_the_mana() {
+        let map = "\
+%%%%%%%
+%@..O.%
+%%%%%%%
+";
+        let d = run(map, Spell::Bolt, (1, 4), 0, 0);
+        assert_eq!(d.monsters[0].hp, 60);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::dungeon::event::Event;
+use crate::dungeon::monster::MonsterKind;
+use crate::dungeon::{kill_monster, push_event, Dungeon};
+use quicksilver::prelude::Color;
+
+/// Most effects one actor can be under at once.
+pub const MAX_STATUSES: usize = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StatusKind {
+    /// Lose `strength` hp a turn.
+    Poison,
+    /// Skip turns.
+    Stun,
+    /// Stumble about in random directions.
+    Confusion,
+    /// Monsters win contested cells; a hasted player gets a free move every
+    /// other turn.
+    Haste,
+    /// Monsters lose every other turn and lose contested cells.
+    Slow,
+    /// Monsters run from the player whatever their hp.
+    Fear,
+}
+
+/// What happens when an effect is added to an actor already under it.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Stacking {
+    /// Keep whichever lasts longer.
+    Refresh,
+    /// Add the new turns to the ones left.
+    Extend,
+    /// Add the strengths up to a limit, and keep whichever lasts longer.
+    Intensify { max_strength: i32 },
+}
+
+pub struct StatusInfo {
+    pub name: &'static str,
+    /// Short label shown next to the health bar.
+    pub label: &'static str,
+    pub color: Color,
+    pub stacking: Stacking,
+    /// Added to a monster's initiative.
+    pub initiative: i32,
+}
+
+// One entry per `StatusKind` variant, in declaration order.
+const STATUS_TABLE: [StatusInfo; 6] = [
+    StatusInfo {
+        name: "poisoned",
+        label: "PSN",
+        color: Color::GREEN,
+        stacking: Stacking::Intensify { max_strength: 3 },
+        initiative: 0,
+    },
+    StatusInfo {
+        name: "stunned",
+        label: "STN",
+        color: Color::YELLOW,
+        stacking: Stacking::Refresh,
+        initiative: 0,
+    },
+    StatusInfo {
+        name: "confused",
+        label: "CNF",
+        color: Color::MAGENTA,
+        stacking: Stacking::Extend,
+        initiative: 0,
+    },
+    StatusInfo {
+        name: "hasted",
+        label: "HST",
+        color: Color::CYAN,
+        stacking: Stacking::Refresh,
+        initiative: 10,
+    },
+    StatusInfo {
+        name: "slowed",
+        label: "SLW",
+        color: Color::BLUE,
+        stacking: Stacking::Refresh,
+        initiative: -10,
+    },
+    StatusInfo {
+        name: "afraid",
+        label: "FER",
+        color: Color::PURPLE,
+        stacking: Stacking::Refresh,
+        initiative: 0,
+    },
+];
+
+impl StatusKind {
+    pub fn info(self) -> &'static StatusInfo {
+        &STATUS_TABLE[self as usize]
+    }
+}
+
+/// One timed effect on an actor.
+#[derive(Copy, Clone, Debug)]
+pub struct Status {
+    pub kind: StatusKind,
+    pub turns: u32,
+    pub strength: i32,
+    /// The monster that caused it, if any, to blame for a death.
+    pub source: Option<MonsterKind>,
+}
+
+/// The effects an actor is under.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Statuses {
+    pub effects: [Option<Status>; MAX_STATUSES],
+}
+
+impl Statuses {
+    pub fn get(&self, kind: StatusKind) -> Option<Status> {
+        self.effects
+            .iter()
+            .flatten()
+            .find(|s| s.kind == kind)
+            .copied()
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.get(kind).is_some()
+    }
+
+    /// Put the actor under an effect, following its stacking rule. When the
+    /// actor is already under as many effects as it can be, the one closest
+    /// to wearing off makes way.
+    pub fn add(&mut self, status: Status) {
+        if let Some(current) = self
+            .effects
+            .iter_mut()
+            .flatten()
+            .find(|s| s.kind == status.kind)
+        {
+            match status.kind.info().stacking {
+                Stacking::Refresh => current.turns = current.turns.max(status.turns),
+                Stacking::Extend => current.turns += status.turns,
+                Stacking::Intensify { max_strength } => {
+                    current.turns = current.turns.max(status.turns);
+                    current.strength = (current.strength + status.strength).min(max_strength);
+                }
+            }
+            if status.source.is_some() {
+                current.source = status.source;
+            }
+            return;
+        }
+        let slot = match self.effects.iter().position(|s| s.is_none()) {
+            Some(free) => free,
+            None => (0..MAX_STATUSES)
+                .min_by_key(|&i| self.effects[i].map_or(0, |s| s.turns))
+                .unwrap_or(0),
+        };
+        self.effects[slot] = Some(status);
+    }
+
+    /// Take an effect off early, as a cure does.
+    pub fn remove(&mut self, kind: StatusKind) {
+        for slot in self.effects.iter_mut() {
+            if slot.is_some_and(|s| s.kind == kind) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Whether the effects cost a monster its turn: stunned monsters lose
+    /// every turn and slowed ones every other.
+    pub fn loses_turn(&self, turn: u32) -> bool {
+        self.has(StatusKind::Stun) || (self.has(StatusKind::Slow) && turn % 2 == 1)
+    }
+
+    /// Total initiative bonus of the effects.
+    pub fn initiative(&self) -> i32 {
+        self.effects
+            .iter()
+            .flatten()
+            .map(|s| s.kind.info().initiative)
+            .sum()
+    }
+
+    /// Count every effect down a turn, returning the ones that wore off.
+    pub fn tick(&mut self) -> Vec<StatusKind> {
+        let mut ended = Vec::new();
+        for slot in self.effects.iter_mut() {
+            if let Some(status) = slot {
+                status.turns = status.turns.saturating_sub(1);
+                if status.turns == 0 {
+                    ended.push(status.kind);
+                    *slot = None;
+                }
+            }
+        }
+        ended
+    }
+}
+
+/// A turn passes for every actor's effects: poison bites, and whatever has
+/// run its course wears off. The player is told when theirs do.
+pub fn tick_statuses(mut d: Dungeon) -> Dungeon {
+    if d.player.alive {
+        if let Some(poison) = d.player.statuses.get(StatusKind::Poison) {
+            d.player.hp -= poison.strength;
+            if d.player.hp <= 0 {
+                d.player.alive = false;
+                d = match poison.source {
+                    Some(kind) => push_event(d, Event::PlayerDied { kind }),
+                    None => push_event(d, Event::PoisonedToDeath),
+                };
+            }
+        }
+        for kind in d.player.statuses.tick() {
+            d = push_event(d, Event::StatusEnded { kind });
+        }
+    }
+
+    for i in 0..d.monsters.len() {
+        if !d.monsters[i].alive {
+            continue;
+        }
+        if let Some(poison) = d.monsters[i].statuses.get(StatusKind::Poison) {
+            d.monsters[i].hp -= poison.strength;
+            if d.monsters[i].hp <= 0 {
+                d = kill_monster(d, i);
+                continue;
+            }
+        }
+        d.monsters[i].statuses.tick();
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ascii_map::load_map;
+    use crate::dungeon::take_events;
+
+    fn poisoned_to_death(source: Option<MonsterKind>) -> Vec<Event> {
+        let mut d = load_map("%%%\n%@%\n%%%\n").expect("status test map loads");
+        d.player.hp = 1;
+        d.player.statuses.add(Status {
+            kind: StatusKind::Poison,
+            turns: 3,
+            strength: 1,
+            source,
+        });
+        let d = tick_statuses(d);
+        assert!(!d.player.alive);
+        take_events(d).1
+    }
+
+    #[test]
+    fn poison_death_names_the_monster() {
+        let events = poisoned_to_death(Some(MonsterKind::Rat));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::PlayerDied {
+                kind: MonsterKind::Rat
+            }
+        )));
+    }
+
+    #[test]
+    fn poison_death_without_a_monster_is_still_reported() {
+        let events = poisoned_to_death(None);
+        assert!(events.iter().any(|e| matches!(e, Event::PoisonedToDeath)));
+    }
+}
@@ -0,0 +1,219 @@
+use quicksilver::prelude::Color;
+
+/// Movement cost reported for terrain nothing can walk through.
+pub const IMPASSABLE: u16 = 1055;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Terrain {
+    Floor,
+    Corridor,
+    Rock,
+    Wall,
+    OpenDoor,
+    ClosedDoor,
+    LockedDoor,
+    /// Down to the level below.
+    Stairs,
+    Water,
+    /// Back up to the level above, or out of the dungeon from the first.
+    UpStairs,
+}
+
+/// Everything the game needs to know about one kind of terrain.
+pub struct TerrainInfo {
+    pub glyph: char,
+    pub passable: bool,
+    pub transparent: bool,
+    pub movement_cost: u16,
+    pub color: Color,
+    pub term_color: colored::Color,
+    /// What the cell turns into when opened, for doors.
+    pub opens_to: Option<Terrain>,
+    /// Whether opening needs a key from the inventory.
+    pub needs_key: bool,
+    /// Whether digging through the cell's hardness turns it into corridor.
+    pub diggable: bool,
+}
+
+// One entry per `Terrain` variant, in declaration order.
+const TERRAIN_TABLE: [TerrainInfo; 10] = [
+    // Floor
+    TerrainInfo {
+        glyph: '.',
+        passable: true,
+        transparent: true,
+        movement_cost: 1,
+        color: Color::BLACK,
+        term_color: colored::Color::Green,
+        opens_to: None,
+        needs_key: false,
+        diggable: false,
+    },
+    // Corridor
+    TerrainInfo {
+        glyph: '#',
+        passable: true,
+        transparent: true,
+        movement_cost: 1,
+        color: Color::BLACK,
+        term_color: colored::Color::Yellow,
+        opens_to: None,
+        needs_key: false,
+        diggable: false,
+    },
+    // Rock
+    TerrainInfo {
+        glyph: ' ',
+        passable: false,
+        transparent: false,
+        movement_cost: IMPASSABLE,
+        color: Color::BLACK,
+        term_color: colored::Color::White,
+        opens_to: None,
+        needs_key: false,
+        diggable: true,
+    },
+    // Wall
+    TerrainInfo {
+        glyph: '%',
+        passable: false,
+        transparent: false,
+        movement_cost: IMPASSABLE,
+        color: Color::BLACK,
+        term_color: colored::Color::White,
+        opens_to: None,
+        needs_key: false,
+        diggable: false,
+    },
+    // OpenDoor
+    TerrainInfo {
+        glyph: '\'',
+        passable: true,
+        transparent: true,
+        movement_cost: 1,
+        color: Color::ORANGE,
+        term_color: colored::Color::Red,
+        opens_to: None,
+        needs_key: false,
+        diggable: false,
+    },
+    // ClosedDoor: opening takes a turn, then the doorway is walked through
+    TerrainInfo {
+        glyph: '+',
+        passable: false,
+        transparent: false,
+        movement_cost: 2,
+        color: Color::ORANGE,
+        term_color: colored::Color::Red,
+        opens_to: Some(Terrain::OpenDoor),
+        needs_key: false,
+        diggable: false,
+    },
+    // LockedDoor
+    TerrainInfo {
+        glyph: '=',
+        passable: false,
+        transparent: false,
+        movement_cost: IMPASSABLE,
+        color: Color::RED,
+        term_color: colored::Color::BrightRed,
+        opens_to: Some(Terrain::OpenDoor),
+        needs_key: true,
+        diggable: false,
+    },
+    // Stairs
+    TerrainInfo {
+        glyph: '>',
+        passable: true,
+        transparent: true,
+        movement_cost: 1,
+        color: Color::PURPLE,
+        term_color: colored::Color::Magenta,
+        opens_to: None,
+        needs_key: false,
+        diggable: false,
+    },
+    // Water
+    TerrainInfo {
+        glyph: '~',
+        passable: true,
+        transparent: true,
+        movement_cost: 3,
+        color: Color::BLUE,
+        term_color: colored::Color::Blue,
+        opens_to: None,
+        needs_key: false,
+        diggable: false,
+    },
+    // UpStairs
+    TerrainInfo {
+        glyph: '<',
+        passable: true,
+        transparent: true,
+        movement_cost: 1,
+        color: Color::PURPLE,
+        term_color: colored::Color::Magenta,
+        opens_to: None,
+        needs_key: false,
+        diggable: false,
+    },
+];
+
+impl Terrain {
+    pub const ALL: [Terrain; 10] = [
+        Terrain::Floor,
+        Terrain::Corridor,
+        Terrain::Rock,
+        Terrain::Wall,
+        Terrain::OpenDoor,
+        Terrain::ClosedDoor,
+        Terrain::LockedDoor,
+        Terrain::Stairs,
+        Terrain::Water,
+        Terrain::UpStairs,
+    ];
+
+    pub fn info(self) -> &'static TerrainInfo {
+        &TERRAIN_TABLE[self as usize]
+    }
+
+    pub fn glyph(self) -> char {
+        self.info().glyph
+    }
+
+    pub fn passable(self) -> bool {
+        self.info().passable
+    }
+
+    pub fn transparent(self) -> bool {
+        self.info().transparent
+    }
+
+    pub fn opens_to(self) -> Option<Terrain> {
+        self.info().opens_to
+    }
+
+    pub fn needs_key(self) -> bool {
+        self.info().needs_key
+    }
+
+    pub fn diggable(self) -> bool {
+        self.info().diggable
+    }
+
+    pub fn movement_cost(self) -> u16 {
+        self.info().movement_cost
+    }
+
+    pub fn color(self) -> Color {
+        self.info().color
+    }
+
+    pub fn term_color(self) -> colored::Color {
+        self.info().term_color
+    }
+
+    pub fn from_glyph(glyph: char) -> Option<Terrain> {
+        Terrain::ALL.iter().copied().find(|t| t.glyph() == glyph)
+    }
+}
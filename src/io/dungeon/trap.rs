@@ -0,0 +1,502 @@
+use crate::dungeon::boss;
+use crate::dungeon::character::Talent;
+use crate::dungeon::consumable;
+use crate::dungeon::event::Event;
+use crate::dungeon::monster::UNPLACED;
+use crate::dungeon::quest;
+use crate::dungeon::terrain::{Terrain, IMPASSABLE};
+use crate::dungeon::{descend, free_floor_cell, kill_monster, push_event, Dungeon};
+use quicksilver::prelude::Color;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Most traps a level can have.
+pub const MAX_TRAPS: usize = 12;
+
+/// Traps on the first level, with one more every other level down.
+const TRAPS_PER_LEVEL: usize = 4;
+
+/// Traps are never closer than this (distance map cost) to where the player
+/// starts.
+const MIN_PLAYER_DISTANCE: u16 = 3;
+
+/// Added to the cost of stepping onto a trap the player has found. Found
+/// traps are plain to see, so monsters go around them when they can.
+pub const AVOID_COST: u16 = 20;
+
+/// Percent chance a turn of spotting each hidden trap next to the player.
+const PASSIVE_CHANCE: u32 = 10;
+
+/// Percent chance a search turns up each hidden trap within its radius.
+const SEARCH_CHANCE: u32 = 50;
+const SEARCH_RADIUS: usize = 2;
+
+/// Mixed into the seed for spotting traps and where they teleport to.
+const TRAP_SALT: u64 = 0x7a9;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrapKind {
+    /// Drops whoever steps on it to the level below.
+    Pit,
+    Dart,
+    /// Wakes every monster on the level and calls them to it.
+    Alarm,
+    /// Moves whoever steps on it to a random floor cell.
+    Teleport,
+}
+
+pub struct TrapInfo {
+    pub name: &'static str,
+    pub glyph: char,
+    pub color: Color,
+    /// How often the generator picks it, against the other kinds.
+    pub weight: u32,
+    /// Hp lost by whoever springs it.
+    pub damage: i32,
+    /// What the player is told when they spring it.
+    pub message: &'static str,
+    /// What the player sees happen to a monster that springs it.
+    pub monster_message: &'static str,
+}
+
+// One entry per `TrapKind` variant, in declaration order.
+const TRAP_TABLE: [TrapInfo; 4] = [
+    TrapInfo {
+        name: "pit",
+        glyph: '^',
+        color: Color::BLACK,
+        weight: 2,
+        damage: 4,
+        message: "The floor gives way beneath you!",
+        monster_message: "tumbles into a pit",
+    },
+    TrapInfo {
+        name: "dart trap",
+        glyph: '^',
+        color: Color::RED,
+        weight: 4,
+        damage: 6,
+        message: "A dart shoots out of the wall at you!",
+        monster_message: "is hit by a dart",
+    },
+    TrapInfo {
+        name: "alarm",
+        glyph: '^',
+        color: Color::ORANGE,
+        weight: 2,
+        damage: 0,
+        message: "An alarm rings out across the level!",
+        monster_message: "sets off an alarm",
+    },
+    TrapInfo {
+        name: "teleport trap",
+        glyph: '^',
+        color: Color::MAGENTA,
+        weight: 2,
+        damage: 0,
+        message: "The world spins around you.",
+        monster_message: "vanishes in a flash",
+    },
+];
+
+impl TrapKind {
+    pub const ALL: [TrapKind; 4] = [
+        TrapKind::Pit,
+        TrapKind::Dart,
+        TrapKind::Alarm,
+        TrapKind::Teleport,
+    ];
+
+    pub fn info(self) -> &'static TrapInfo {
+        &TRAP_TABLE[self as usize]
+    }
+}
+
+/// A trap on the map, hidden until the player finds it.
+#[derive(Copy, Clone)]
+pub struct Trap {
+    pub kind: TrapKind,
+    pub pos_x: usize,
+    pub pos_y: usize,
+    pub found: bool,
+}
+
+/// Slot of the trap at a cell, if there is one.
+pub fn trap_at(d: &Dungeon, x: usize, y: usize) -> Option<usize> {
+    d.traps
+        .iter()
+        .position(|t| t.is_some_and(|t| (t.pos_x, t.pos_y) == (x, y)))
+}
+
+/// The cells with a trap the player has found.
+pub fn known_traps(d: &Dungeon) -> [[bool; 80]; 30] {
+    let mut known = [[false; 80]; 30];
+    for trap in d.traps.iter().flatten().filter(|t| t.found) {
+        known[trap.pos_x][trap.pos_y] = true;
+    }
+    known
+}
+
+/// Hide traps on floor and corridor cells away from the player's start,
+/// keeping clear of items, monsters and the shop.
+pub fn place_traps(mut d: Dungeon, rng: &mut StdRng) -> Dungeon {
+    let count = (TRAPS_PER_LEVEL + (d.depth as usize - 1) / 2).min(MAX_TRAPS);
+    let total: u32 = TrapKind::ALL.iter().map(|k| k.info().weight).sum();
+    for slot in 0..count {
+        let mut pick = rng.gen_range(0..total);
+        let kind = TrapKind::ALL
+            .iter()
+            .copied()
+            .find(|k| {
+                let weight = k.info().weight;
+                pick < weight || {
+                    pick -= weight;
+                    false
+                }
+            })
+            .unwrap_or(TrapKind::Dart);
+        // There is nowhere to fall to from the deepest level
+        let kind = match kind {
+            TrapKind::Pit if quest::is_deepest(&d) => TrapKind::Dart,
+            kind => kind,
+        };
+
+        let cells: Vec<(usize, usize)> = (1..d.dungeon_x)
+            .flat_map(|x| (1..d.dungeon_y).map(move |y| (x, y)))
+            .filter(|&(x, y)| {
+                let terrain = d.terrain_map[x][y];
+                let distance = d.distance_map[x][y];
+                let item = d
+                    .items
+                    .iter()
+                    .flatten()
+                    .any(|i| (i.pos_x, i.pos_y) == (x, y));
+                let monster = d
+                    .monsters
+                    .iter()
+                    .any(|m| m.pos_x != UNPLACED && (m.pos_x, m.pos_y) == (x, y));
+                let shop = d.shop.is_some_and(|shop| shop.contains(x, y));
+                (terrain == Terrain::Floor || terrain == Terrain::Corridor)
+                    && (MIN_PLAYER_DISTANCE..IMPASSABLE).contains(&distance)
+                    && !item
+                    && !monster
+                    && !shop
+                    && trap_at(&d, x, y).is_none()
+            })
+            .collect();
+        if cells.is_empty() {
+            break;
+        }
+        let (x, y) = cells[rng.gen_range(0..cells.len())];
+        d.traps[slot] = Some(Trap {
+            kind,
+            pos_x: x,
+            pos_y: y,
+            found: false,
+        });
+    }
+    d
+}
+
+/// Mark a trap found, counting it for the run if it was hidden.
+fn reveal(mut d: Dungeon, slot: usize) -> Dungeon {
+    if let Some(trap) = d.traps[slot].as_mut() {
+        if !trap.found {
+            trap.found = true;
+            d.stats.traps_found += 1;
+        }
+    }
+    d
+}
+
+/// Wake every monster on the level and send them to a cell.
+fn sound_alarm(mut d: Dungeon, at: (usize, usize)) -> Dungeon {
+    for monster in d.monsters.iter_mut().filter(|m| m.alive) {
+        monster.awake = true;
+        monster.last_seen = Some(at);
+    }
+    d
+}
+
+/// Spring the trap under the player, if there is one, which reveals it.
+pub fn enter_cell(mut d: Dungeon) -> Dungeon {
+    let Some(slot) = trap_at(&d, d.player.position_x, d.player.position_y) else {
+        return d;
+    };
+    let Some(trap) = d.traps[slot] else {
+        return d;
+    };
+    d = reveal(d, slot);
+    let damage = trap.kind.info().damage;
+    d = push_event(
+        d,
+        Event::TrapSprung {
+            kind: trap.kind,
+            damage,
+        },
+    );
+    d.player.hp -= damage;
+    if d.player.hp <= 0 {
+        d.player.alive = false;
+        return push_event(d, Event::KilledByTrap { kind: trap.kind });
+    }
+    match trap.kind {
+        TrapKind::Pit => descend(d),
+        TrapKind::Dart => d,
+        TrapKind::Alarm => sound_alarm(d, (trap.pos_x, trap.pos_y)),
+        TrapKind::Teleport => consumable::teleport(d),
+    }
+}
+
+/// Spring the traps under monsters that stepped onto one this turn, given
+/// where every monster stood before moving. Traps sprung in sight of the
+/// player are revealed. Bosses are too big to fall through a pit.
+pub fn spring_monster_traps(mut d: Dungeon, before: &[(usize, usize)]) -> Dungeon {
+    let player = (d.player.position_x, d.player.position_y);
+    let mut rng = StdRng::seed_from_u64(d.seed ^ ((d.turn as u64) << 8) ^ TRAP_SALT);
+    for (i, &start) in before.iter().enumerate() {
+        let monster = d.monsters[i];
+        let at = (monster.pos_x, monster.pos_y);
+        if !monster.alive || at == start {
+            continue;
+        }
+        let Some(slot) = trap_at(&d, at.0, at.1) else {
+            continue;
+        };
+        let Some(trap) = d.traps[slot] else {
+            continue;
+        };
+        if d.line_of_sight(player, at) {
+            d = reveal(d, slot);
+            d = push_event(
+                d,
+                Event::MonsterSprungTrap {
+                    kind: monster.kind,
+                    trap: trap.kind,
+                },
+            );
+        }
+        if trap.kind == TrapKind::Pit && boss::boss_info(monster.kind).is_none() {
+            d.mon_map[at.0][at.1] = false;
+            d.monsters[i].park();
+            continue;
+        }
+        d.monsters[i].hp -= trap.kind.info().damage;
+        if d.monsters[i].hp <= 0 {
+            d = kill_monster(d, i);
+            continue;
+        }
+        match trap.kind {
+            TrapKind::Pit | TrapKind::Dart => {}
+            TrapKind::Alarm => d = sound_alarm(d, at),
+            TrapKind::Teleport => {
+                let Some((x, y)) = free_floor_cell(&d, &mut rng) else {
+                    continue;
+                };
+                d.mon_map[at.0][at.1] = false;
+                d.mon_map[x][y] = true;
+                d.monsters[i].pos_x = x;
+                d.monsters[i].pos_y = y;
+            }
+        }
+    }
+    d
+}
+
+/// Roll to find each hidden trap within a radius of the player. Returns
+/// whether any were found.
+fn look_for_traps(mut d: Dungeon, radius: usize, chance: u32, salt: u64) -> (Dungeon, bool) {
+    let chance = if d.player.has_talent(Talent::KeenEyes) {
+        chance * 2
+    } else {
+        chance
+    };
+    let mut rng = StdRng::seed_from_u64(d.seed ^ ((d.turn as u64) << 8) ^ TRAP_SALT ^ salt);
+    let (px, py) = (d.player.position_x, d.player.position_y);
+    let mut found = false;
+    for slot in 0..d.traps.len() {
+        let Some(trap) = d.traps[slot] else {
+            continue;
+        };
+        let near = trap.pos_x.abs_diff(px).max(trap.pos_y.abs_diff(py)) <= radius;
+        if trap.found || !near || rng.gen_range(0..100) >= chance {
+            continue;
+        }
+        d = reveal(d, slot);
+        d = push_event(d, Event::TrapFound { kind: trap.kind });
+        found = true;
+    }
+    (d, found)
+}
+
+/// The player may notice hidden traps right next to them as they go.
+pub fn notice_traps(d: Dungeon) -> Dungeon {
+    look_for_traps(d, 1, PASSIVE_CHANCE, 0).0
+}
+
+/// Spend a turn looking for hidden traps nearby.
+pub fn search(d: Dungeon) -> Dungeon {
+    let (d, found) = look_for_traps(d, SEARCH_RADIUS, SEARCH_CHANCE, 0x5e);
+    if found {
+        d
+    } else {
+        push_event(d, Event::NothingFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::ability::MAX_ABILITIES;
+    use crate::dungeon::ascii_map::{export_map, load_map};
+    use crate::dungeon::spell::{self, Spell};
+    use crate::dungeon::{calculate_distance_map, monster_map, take_turn};
+
+    /// Load a map, lay traps on it as (kind, x, y, found) and play a few
+    /// turns. As in the movement tests, every monster knows where the player
+    /// is and keeps its abilities on cooldown.
+    fn run(map: &str, traps: &[(TrapKind, usize, usize, bool)], turns: usize) -> Dungeon {
+        let mut d = load_map(map).expect("trap test map loads");
+        for (slot, &(kind, x, y, found)) in traps.iter().enumerate() {
+            d.traps[slot] = Some(Trap {
+                kind,
+                pos_x: x,
+                pos_y: y,
+                found,
+            });
+        }
+        d = calculate_distance_map(d);
+        let player = (d.player.position_x, d.player.position_y);
+        for monster in d.monsters.iter_mut().filter(|m| m.alive) {
+            monster.last_seen = Some(player);
+            monster.ability_cooldowns = [u32::MAX; MAX_ABILITIES];
+        }
+        for _ in 0..turns {
+            d = monster_map(d);
+            d = take_turn(d);
+        }
+        d
+    }
+
+    fn all_found(d: &Dungeon) -> bool {
+        d.traps.iter().flatten().all(|t| t.found)
+    }
+
+    const ROOM: &str = "\
+%%%%%%%%%%
+%@.....g.%
+%........%
+%%%%%%%%%%
+";
+
+    #[test]
+    fn goblin_steps_around_a_found_trap() {
+        let d = run(ROOM, &[(TrapKind::Dart, 1, 6, true)], 1);
+        assert_eq!(
+            export_map(&d),
+            "\
+%%%%%%%%%%
+%@.......%
+%.....g..%
+%%%%%%%%%%
+"
+        );
+    }
+
+    #[test]
+    fn goblin_reveals_a_hidden_trap_in_sight() {
+        let d = run(ROOM, &[(TrapKind::Dart, 1, 6, false)], 1);
+        assert_eq!(
+            export_map(&d),
+            "\
+%%%%%%%%%%
+%@....g..%
+%........%
+%%%%%%%%%%
+"
+        );
+        assert!(all_found(&d));
+    }
+
+    #[test]
+    fn rat_falls_through_a_pit() {
+        let map = "\
+%%%%%%%%
+%@...r.%
+%%%%%%%%
+";
+        let d = run(map, &[(TrapKind::Pit, 1, 4, false)], 1);
+        assert_eq!(
+            export_map(&d),
+            "\
+%%%%%%%%
+%@.....%
+%%%%%%%%
+"
+        );
+        assert!(all_found(&d));
+    }
+
+    #[test]
+    fn goblin_still_crosses_a_found_trap_in_the_only_corridor() {
+        let map = "\
+%%%%%%%%%%
+%@..#.#g.%
+%%%%%%%%%%
+";
+        let d = run(map, &[(TrapKind::Alarm, 1, 5, true)], 2);
+        assert_eq!(
+            export_map(&d),
+            "\
+%%%%%%%%%%
+%@..#g#..%
+%%%%%%%%%%
+"
+        );
+    }
+
+    #[test]
+    fn teleported_monster_takes_its_place_on_the_monster_map() {
+        let mut d = monster_map(run(ROOM, &[(TrapKind::Teleport, 1, 6, false)], 0));
+        let before: Vec<(usize, usize)> = d.monsters.iter().map(|m| (m.pos_x, m.pos_y)).collect();
+        d.monsters[0].pos_y = 6;
+        d.mon_map[1][7] = false;
+        d.mon_map[1][6] = true;
+        let d = spring_monster_traps(d, &before);
+        let (x, y) = (d.monsters[0].pos_x, d.monsters[0].pos_y);
+        assert_ne!((x, y), (1, 6));
+        assert!(!d.mon_map[1][6]);
+        assert!(d.mon_map[x][y]);
+    }
+
+    #[test]
+    fn blinking_onto_a_hidden_trap_springs_it() {
+        let mut d = run(ROOM, &[(TrapKind::Dart, 2, 4, false)], 0);
+        d.player.spells[0] = Some(Spell::Blink);
+        d.player.max_mana = 100;
+        d.player.mana = 100;
+        let d = spell::cast(d, Spell::Blink, (2, 4)).0;
+        assert_eq!((d.player.position_x, d.player.position_y), (2, 4));
+        assert_eq!(d.player.hp, 100 - TrapKind::Dart.info().damage);
+        assert!(all_found(&d));
+    }
+
+    #[test]
+    fn teleport_with_nowhere_to_go_leaves_the_monster_be() {
+        let map = "\
+%%%%%%%%
+%@###g#%
+%%%%%%%%
+";
+        let mut d = monster_map(run(map, &[(TrapKind::Teleport, 1, 4, false)], 0));
+        let before: Vec<(usize, usize)> = d.monsters.iter().map(|m| (m.pos_x, m.pos_y)).collect();
+        // Monsters in a map file stand on floor, which the goblin has left
+        d.terrain_map[1][5] = Terrain::Corridor;
+        d.monsters[0].pos_y = 4;
+        d.mon_map[1][5] = false;
+        d.mon_map[1][4] = true;
+        let d = spring_monster_traps(d, &before);
+        assert_eq!((d.monsters[0].pos_x, d.monsters[0].pos_y), (1, 4));
+        assert!(d.mon_map[1][4]);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::dungeon::item::ItemKind;
+use crate::dungeon::monster::{Monster, MonsterKind, UNPLACED};
+use crate::dungeon::terrain::Terrain;
+use crate::dungeon::{drop_item, set_terrain, Dungeon};
+use rand::rngs::StdRng;
+use rand::Rng;
+
+/// A hand-drawn room layout the generator can stamp in place of a plain room.
+#[derive(Clone)]
+pub struct Vault {
+    pub cells: Vec<Vec<char>>,
+}
+
+impl Vault {
+    pub fn height(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn width(&self) -> usize {
+        self.cells[0].len()
+    }
+
+    /// The vault turned a quarter turn clockwise.
+    pub fn rotated(&self) -> Vault {
+        let cells = (0..self.width())
+            .map(|y| (0..self.height()).rev().map(|x| self.cells[x][y]).collect())
+            .collect();
+        Vault { cells }
+    }
+
+    /// The vault flipped left to right.
+    pub fn mirrored(&self) -> Vault {
+        let cells = self
+            .cells
+            .iter()
+            .map(|row| row.iter().rev().copied().collect())
+            .collect();
+        Vault { cells }
+    }
+
+    /// One of the eight rotations and reflections of the vault.
+    pub fn random_orientation(&self, rng: &mut StdRng) -> Vault {
+        let mut vault = self.clone();
+        for _ in 0..rng.gen_range(0..4) {
+            vault = vault.rotated();
+        }
+        if rng.gen_bool(0.5) {
+            vault = vault.mirrored();
+        }
+        vault
+    }
+}
+
+/// The vault templates shipped in `static/vaults.txt`.
+pub fn library() -> Vec<Vault> {
+    parse_vaults(include_str!("../../../static/vaults.txt"))
+        .expect("Could not parse the vault templates.")
+}
+
+/// Templates are blocks of map glyphs separated by blank or `;` comment lines.
+pub fn parse_vaults(text: &str) -> Result<Vec<Vault>, String> {
+    let mut vaults = Vec::new();
+    let mut cells: Vec<Vec<char>> = Vec::new();
+
+    for line in text.lines().chain(std::iter::once("")) {
+        if line.trim().is_empty() || line.starts_with(';') {
+            if !cells.is_empty() {
+                vaults.push(check_vault(Vault { cells })?);
+                cells = Vec::new();
+            }
+        } else {
+            cells.push(line.chars().collect());
+        }
+    }
+    Ok(vaults)
+}
+
+fn check_vault(vault: Vault) -> Result<Vault, String> {
+    let (height, width) = (vault.height(), vault.width());
+    for (x, row) in vault.cells.iter().enumerate() {
+        if row.len() != width {
+            return Err(format!("vault row {:?} is not {} wide", row.iter().collect::<String>(), width));
+        }
+        for (y, &glyph) in row.iter().enumerate() {
+            let known = Terrain::from_glyph(glyph).is_some()
+                || MonsterKind::from_glyph(glyph).is_some()
+                || ItemKind::from_glyph(glyph).is_some();
+            if !known {
+                return Err(format!("unknown glyph '{}' in vault", glyph));
+            }
+            let edge = x == 0 || x == height - 1 || y == 0 || y == width - 1;
+            if edge && glyph != Terrain::Floor.glyph() {
+                return Err(String::from("vault must be ringed with floor"));
+            }
+        }
+    }
+    Ok(vault)
+}
+
+/// Copy a vault's terrain, monsters and items into the dungeon with its top
+/// left corner at (x, y). Monsters take free roster slots while there are any,
+/// and start asleep guarding the vault.
+pub fn stamp(mut d: Dungeon, x: usize, y: usize, vault: &Vault) -> Dungeon {
+    for (i, row) in vault.cells.iter().enumerate() {
+        for (j, &glyph) in row.iter().enumerate() {
+            let (cx, cy) = (x + i, y + j);
+            if let Some(terrain) = Terrain::from_glyph(glyph) {
+                d = set_terrain(d, cx, cy, terrain);
+                continue;
+            }
+
+            d = set_terrain(d, cx, cy, Terrain::Floor);
+            if let Some(kind) = MonsterKind::from_glyph(glyph) {
+                if let Some(slot) = d.monsters.iter().position(|m| m.pos_x == UNPLACED) {
+                    d.monsters[slot] = Monster::new(kind);
+                    d.monsters[slot].pos_x = cx;
+                    d.monsters[slot].pos_y = cy;
+                    d.monsters[slot].awake = false;
+                }
+            } else if let Some(kind) = ItemKind::from_glyph(glyph) {
+                d = drop_item(d, kind, cx, cy).0;
+            }
+        }
+    }
+    d
+}
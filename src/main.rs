@@ -3,6 +3,36 @@ use crate::io::*;
 use quicksilver::prelude::*;
 
 pub fn main() {
+    // `--check-loot N` is a self check that rolls every loot table N times
+    // and reports the odds, and `--characters` lists the races and classes
+    // to pick with `--race` and `--class`.
+    let args: Vec<String> = std::env::args().collect();
+    let passed = match args.get(1).map(String::as_str) {
+        Some("--check-loot") => match args.get(2).map(|rolls| rolls.parse()) {
+            Some(Ok(rolls)) => Some(dungeon::loot::check_loot(rolls)),
+            _ => {
+                eprintln!("usage: --check-loot N, where N is how many times to roll");
+                Some(false)
+            }
+        },
+        Some("--characters") => {
+            let definitions = dungeon::character::definitions();
+            println!("Races (--race NAME):");
+            for race in definitions.races.iter() {
+                println!("  {}", race.summary());
+            }
+            println!("Classes (--class NAME), added to the race's stats:");
+            for class in definitions.classes.iter() {
+                println!("  {}", class.summary());
+            }
+            Some(true)
+        }
+        _ => None,
+    };
+    if let Some(passed) = passed {
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
     std::env::set_var("WINIT_HIDPI_FACTOR", "1.0");
 
     let settings = Settings {